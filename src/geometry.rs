@@ -1,4 +1,4 @@
-use std::iter;
+use std::{cell::Cell, collections::HashMap, io};
 
 use glium::{
     backend::Facade,
@@ -9,7 +9,29 @@ use glium::{
 use nalgebra::Vector3;
 use num::Bounded;
 
-use crate::ply::Document;
+use self::cache::CachedGeometry;
+use crate::{obj, ply};
+
+pub use self::bounds::{BoundingSphere, HasPosition};
+pub use self::colormap::{apply_colormap, Colormap, ScalarRange};
+pub use self::curvature::{gaussian_curvature, mean_curvature};
+pub use self::distance::{ClosestPoint, MeshDistance};
+pub use self::marching_cubes::{isosurface, marching_cubes, ScalarGrid};
+pub use self::smoothing::{
+    laplacian_smooth, recompute_normals, taubin_smooth, LaplacianWeights, SmoothingOptions,
+};
+pub use self::tangents::with_tangents;
+pub use self::terrain::{HeightField, TerrainOptions};
+
+pub mod bounds;
+pub mod cache;
+pub mod colormap;
+pub mod curvature;
+pub mod distance;
+pub mod marching_cubes;
+pub mod smoothing;
+pub mod tangents;
+pub mod terrain;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct PCNVertex {
@@ -30,14 +52,73 @@ impl PCNVertex {
 
 implement_vertex!(PCNVertex, position, color, normal);
 
+impl From<PCNTVertex> for PCNVertex {
+    fn from(v: PCNTVertex) -> PCNVertex {
+        PCNVertex {
+            position: v.position,
+            color: v.color,
+            normal: v.normal,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PCNTVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+    pub normal: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+
+impl PCNTVertex {
+    pub fn position_vec(&self) -> Vector3<f32> {
+        Vector3::from_row_slice(&self.position)
+    }
+
+    pub fn normal_vec(&self) -> Vector3<f32> {
+        Vector3::from_row_slice(&self.normal)
+    }
+}
+
+implement_vertex!(PCNTVertex, position, color, normal, tex_coords);
+
+impl From<PCNVertex> for PCNTVertex {
+    fn from(v: PCNVertex) -> PCNTVertex {
+        PCNTVertex {
+            position: v.position,
+            color: v.color,
+            normal: v.normal,
+            tex_coords: [0.0, 0.0],
+        }
+    }
+}
+
+/// A textured vertex with a tangent, for normal mapping. The w
+/// component of the tangent is the handedness of the tangent frame
+/// (see `tangents::with_tangents`).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PCNTTVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+    pub normal: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub tangent: [f32; 4],
+}
+
+implement_vertex!(PCNTTVertex, position, color, normal, tex_coords, tangent);
+
 pub struct Geometry<V: Vertex, I: Index> {
     // vertices: Vec<V>,
     // indices: Vec<I>,
     vertex_buffer: VertexBuffer<V>,
     index_buffer: IndexBuffer<I>,
+    bounds: Cell<Option<BoundingSphere>>,
 }
 
 impl<V: Vertex, I: Index> Geometry<V, I> {
+    /// Uploads the vertices and indices. The geometry has no bounds
+    /// (so it's never culled) until they're set with `set_bounds`; use
+    /// `with_bounds` to compute them from the vertices.
     pub fn new<F, IV, II>(
         facade: &F,
         draw_type: PrimitiveType,
@@ -55,11 +136,65 @@ impl<V: Vertex, I: Index> Geometry<V, I> {
         Geometry {
             vertex_buffer: VertexBuffer::new(facade, &vertices).unwrap(),
             index_buffer: IndexBuffer::new(facade, draw_type, &indices).unwrap(),
+            bounds: Cell::new(None),
             // vertices: vertices,
             // indices: indices,
         }
     }
 
+    /// Like `new`, but the vertices are kept in a buffer meant to be
+    /// rewritten often (e.g. every frame, for physics-driven
+    /// deformation) with `update_vertices`.
+    pub fn dynamic<F, IV, II>(
+        facade: &F,
+        draw_type: PrimitiveType,
+        into_vertices: IV,
+        into_indices: II,
+    ) -> Geometry<V, I>
+    where
+        F: Facade,
+        IV: Into<Vec<V>>,
+        II: Into<Vec<I>>,
+    {
+        let vertices = into_vertices.into();
+        let indices = into_indices.into();
+
+        Geometry {
+            vertex_buffer: VertexBuffer::dynamic(facade, &vertices).unwrap(),
+            index_buffer: IndexBuffer::new(facade, draw_type, &indices).unwrap(),
+            bounds: Cell::new(None),
+        }
+    }
+
+    /// A sphere around the vertices as they were created, if the
+    /// geometry was made `with_bounds`. Updating the vertices doesn't
+    /// change it; use `set_bounds` for that.
+    pub fn bounds(&self) -> Option<BoundingSphere> {
+        self.bounds.get()
+    }
+
+    pub fn set_bounds(&self, bounds: Option<BoundingSphere>) {
+        self.bounds.set(bounds);
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_buffer.len()
+    }
+
+    /// Overwrites the vertices starting at `first` with `vertices`,
+    /// leaving the rest as they are. The number of vertices can't
+    /// change.
+    pub fn update_vertices(&self, first: usize, vertices: &[V]) {
+        let range = first..(first + vertices.len());
+        assert!(
+            range.end <= self.vertex_count(),
+            "Vertex update {:?} out of range for {} vertices",
+            range,
+            self.vertex_count()
+        );
+        self.vertex_buffer.slice(range).unwrap().write(vertices);
+    }
+
     pub fn vertex_buffer(&self) -> &VertexBuffer<V> {
         &self.vertex_buffer
     }
@@ -69,6 +204,46 @@ impl<V: Vertex, I: Index> Geometry<V, I> {
     }
 }
 
+impl<V: Vertex + HasPosition, I: Index> Geometry<V, I> {
+    /// Like `new`, with bounds around the vertices.
+    pub fn with_bounds<F, IV, II>(
+        facade: &F,
+        draw_type: PrimitiveType,
+        into_vertices: IV,
+        into_indices: II,
+    ) -> Geometry<V, I>
+    where
+        F: Facade,
+        IV: Into<Vec<V>>,
+        II: Into<Vec<I>>,
+    {
+        let vertices = into_vertices.into();
+        let bounds = BoundingSphere::from_vertices(&vertices);
+        let geometry = Geometry::new(facade, draw_type, vertices, into_indices);
+        geometry.set_bounds(bounds);
+        geometry
+    }
+
+    /// Like `dynamic`, with bounds around the initial vertices.
+    pub fn dynamic_with_bounds<F, IV, II>(
+        facade: &F,
+        draw_type: PrimitiveType,
+        into_vertices: IV,
+        into_indices: II,
+    ) -> Geometry<V, I>
+    where
+        F: Facade,
+        IV: Into<Vec<V>>,
+        II: Into<Vec<I>>,
+    {
+        let vertices = into_vertices.into();
+        let bounds = BoundingSphere::from_vertices(&vertices);
+        let geometry = Geometry::dynamic(facade, draw_type, vertices, into_indices);
+        geometry.set_bounds(bounds);
+        geometry
+    }
+}
+
 fn find_or_add_vert<T: Vertex + PartialEq>(verts: &mut Vec<T>, new_vert: T) -> usize {
     match verts.iter().position(|v| *v == new_vert) {
         Some(index) => index,
@@ -124,18 +299,26 @@ pub fn octohedron<F: Facade>(facade: &F) -> Geometry<PCNVertex, u16> {
         elems.push(find_or_add_vert(&mut verts, new_v3) as u16);
     }
 
-    Geometry::new(facade, PrimitiveType::TrianglesList, verts, elems)
+    Geometry::with_bounds(facade, PrimitiveType::TrianglesList, verts, elems)
+}
+
+/// The octohedron's vertices and triangles, without the per-face
+/// normals `octohedron` adds, for tests of the mesh algorithms.
+#[cfg(test)]
+pub(crate) fn octohedron_data() -> (Vec<PCNVertex>, Vec<u32>) {
+    let elems = OCTOHEDRON_ELEMENTS.iter().map(|&i| i as u32).collect();
+    (OCTOHEDRON_VERTICES.to_vec(), elems)
 }
 
 static WIREFRAME_CUBE_VERTICES: [PCNVertex; 8] = [
-    PCNVertex { position: [  1.0,   1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.577350269,   0.577350269,   0.577350269 ] }, // 0
-    PCNVertex { position: [  1.0,   1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.577350269,   0.577350269,  -0.577350269 ] }, // 1
-    PCNVertex { position: [  1.0,  -1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.577350269,  -0.577350269,   0.577350269 ] }, // 2
-    PCNVertex { position: [  1.0,  -1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.577350269,  -0.577350269,  -0.577350269 ] }, // 3
-    PCNVertex { position: [ -1.0,   1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.577350269,   0.577350269,   0.577350269 ] }, // 4
-    PCNVertex { position: [ -1.0,   1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.577350269,   0.577350269,  -0.577350269 ] }, // 5
-    PCNVertex { position: [ -1.0,  -1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.577350269,  -0.577350269,   0.577350269 ] }, // 6
-    PCNVertex { position: [ -1.0,  -1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.577350269,  -0.577350269,  -0.577350269 ] }, // 7
+    PCNVertex { position: [  1.0,   1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.57735026,   0.57735026,   0.57735026 ] }, // 0
+    PCNVertex { position: [  1.0,   1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.57735026,   0.57735026,  -0.57735026 ] }, // 1
+    PCNVertex { position: [  1.0,  -1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.57735026,  -0.57735026,   0.57735026 ] }, // 2
+    PCNVertex { position: [  1.0,  -1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [  0.57735026,  -0.57735026,  -0.57735026 ] }, // 3
+    PCNVertex { position: [ -1.0,   1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.57735026,   0.57735026,   0.57735026 ] }, // 4
+    PCNVertex { position: [ -1.0,   1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.57735026,   0.57735026,  -0.57735026 ] }, // 5
+    PCNVertex { position: [ -1.0,  -1.0,   1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.57735026,  -0.57735026,   0.57735026 ] }, // 6
+    PCNVertex { position: [ -1.0,  -1.0,  -1.0 ], color: [ 1.0, 1.0, 1.0, 1.0 ], normal: [ -0.57735026,  -0.57735026,  -0.57735026 ] }, // 7
 ];
 
 static WIREFRAME_CUBE_ELEMENTS: [usize; 24] = [
@@ -144,53 +327,290 @@ static WIREFRAME_CUBE_ELEMENTS: [usize; 24] = [
     1, 3, 3, 7, 7, 5, 5, 1,
 ];
 
-pub fn wireframe_cube<F: Facade>(facade: &F) -> Geometry<PCNVertex, u32> {
-    let verts: Vec<PCNVertex> = WIREFRAME_CUBE_VERTICES.to_vec();
+pub fn wireframe_cube<F, V>(facade: &F) -> Geometry<V, u32>
+where
+    F: Facade,
+    V: Vertex + HasPosition + From<PCNVertex>,
+{
+    let verts: Vec<V> = WIREFRAME_CUBE_VERTICES.iter().map(|v| V::from(*v)).collect();
     let elems: Vec<u32> = WIREFRAME_CUBE_ELEMENTS.iter().map(|e| *e as u32).collect();
-    Geometry::new(facade, PrimitiveType::LinesList, verts, elems)
+    Geometry::with_bounds(facade, PrimitiveType::LinesList, verts, elems)
+}
+
+pub fn load_ply<F: Facade>(facade: &F, filename: &str) -> io::Result<Geometry<PCNVertex, u32>> {
+    Ok(read_ply(filename)?.to_geometry(facade))
 }
 
-pub fn load_ply<F: Facade>(facade: &F, filename: &str) -> Geometry<PCNVertex, u32> {
-    let doc = Document::from_file(filename).unwrap();
+/// The contents of a ply file, processed but not yet uploaded, so
+/// that they can be modified (e.g. colormapped) first.
+#[derive(Clone, Debug)]
+pub struct PlyData {
+    pub draw_type: PrimitiveType,
+    pub vertices: Vec<PCNVertex>,
+    pub indices: Vec<u32>,
 
-    // Copy the vertex attributes.
-    let vert_elem = doc
-        .elements()
-        .iter()
-        .find(|e| e.name() == "vertex")
-        .unwrap();
-    let mut verts: Vec<PCNVertex> = iter::repeat(Default::default())
-        .take(vert_elem.count() as usize)
-        .collect();
+    /// The per-vertex scalar properties which aren't part of the
+    /// vertex format, e.g. `quality` or `confidence`, by name.
+    pub scalars: HashMap<String, Vec<f32>>,
+}
+
+impl PlyData {
+    pub fn to_geometry<F: Facade>(&self, facade: &F) -> Geometry<PCNVertex, u32> {
+        Geometry::with_bounds(
+            facade,
+            self.draw_type,
+            self.vertices.clone(),
+            self.indices.clone(),
+        )
+    }
+}
+
+pub fn read_ply(filename: &str) -> io::Result<PlyData> {
+    let data = cached_ply_data(filename)?;
+    Ok(PlyData {
+        draw_type: data.draw_type,
+        vertices: data.vertices.into_iter().map(PCNVertex::from).collect(),
+        indices: data.indices,
+        scalars: data.scalars,
+    })
+}
+
+pub fn load_textured_ply<F: Facade>(
+    facade: &F,
+    filename: &str,
+) -> io::Result<Geometry<PCNTVertex, u32>> {
+    let data = cached_ply_data(filename)?;
+    Ok(Geometry::with_bounds(facade, data.draw_type, data.vertices, data.indices))
+}
+
+fn cached_ply_data(filename: &str) -> io::Result<CachedGeometry> {
+    cache::load_cached(filename, "ply", |source| {
+        let doc = ply::Document::from_reader(source)?;
+        let (draw_type, vertices, indices) = ply_geometry_data(&doc)?;
+        Ok(CachedGeometry {
+            draw_type,
+            vertices,
+            indices,
+            scalars: ply_vertex_scalars(&doc)?,
+        })
+    })
+}
+
+static PLY_VERTEX_PROPERTIES: [&str; 16] = [
+    "x", "y", "z", "nx", "ny", "nz", "red", "green", "blue", "alpha",
+    "s", "u", "texture_u", "t", "v", "texture_v",
+];
+
+fn ply_element<'a>(doc: &'a ply::Document, name: &str) -> Option<&'a ply::Element> {
+    doc.elements().iter().find(|e| e.name() == name)
+}
+
+fn ply_vertex_element(doc: &ply::Document) -> io::Result<&ply::Element> {
+    ply_element(doc, "vertex").ok_or_else(|| io::Error::other("PLY file has no vertex element"))
+}
+
+// The values of a property the vertex format needs to be floats.
+fn ply_floats(p: &ply::Property) -> io::Result<&Vec<f64>> {
+    p.data().float_scalar().ok_or_else(|| {
+        io::Error::other(format!("PLY vertex property {:?} isn't a float", p.name()))
+    })
+}
+
+// The values of a property the vertex format needs to be integers.
+fn ply_ints(p: &ply::Property) -> io::Result<&Vec<i64>> {
+    p.data().int_scalar().ok_or_else(|| {
+        io::Error::other(format!("PLY vertex property {:?} isn't an integer", p.name()))
+    })
+}
+
+// The values of a scalar property, whether it's stored as floats or
+// integers.
+fn ply_scalar_values(p: &ply::Property) -> Option<Vec<f32>> {
+    match p.data() {
+        ply::PropertyValue::FloatScalar(fs) => Some(fs.iter().map(|f| *f as f32).collect()),
+        ply::PropertyValue::IntScalar(is) => Some(is.iter().map(|i| *i as f32).collect()),
+        _ => None,
+    }
+}
+
+// The values of a property the vertex format needs to be numbers.
+fn ply_numbers(p: &ply::Property) -> io::Result<Vec<f32>> {
+    ply_scalar_values(p).ok_or_else(|| {
+        io::Error::other(format!("PLY vertex property {:?} isn't a number", p.name()))
+    })
+}
+
+fn ply_vertex_scalars(doc: &ply::Document) -> io::Result<HashMap<String, Vec<f32>>> {
+    let vert_elem = ply_vertex_element(doc)?;
+
+    let mut scalars = HashMap::new();
+    for p in vert_elem.properties() {
+        if PLY_VERTEX_PROPERTIES.contains(&p.name()) {
+            continue;
+        }
+
+        if let Some(values) = ply_scalar_values(p) {
+            scalars.insert(p.name().to_string(), values);
+        }
+    }
+    Ok(scalars)
+}
+
+fn ply_geometry_data(
+    doc: &ply::Document,
+) -> io::Result<(PrimitiveType, Vec<PCNTVertex>, Vec<u32>)> {
+    // Copy the vertex attributes. The colors are 0-255 integers in
+    // the file; default to opaque white if it doesn't have them.
+    let vert_elem = ply_vertex_element(doc)?;
+    let blank_vert = PCNTVertex {
+        color: [255.0; 4],
+        ..Default::default()
+    };
+    let mut verts: Vec<PCNTVertex> = vec![blank_vert; vert_elem.count() as usize];
     for p in vert_elem.properties() {
         match p.name() {
-            "x" => for (v, p) in verts.iter_mut().zip(p.data().float_scalar().unwrap().iter()) { v.position[0] = *p as f32 },
-            "y" => for (v, p) in verts.iter_mut().zip(p.data().float_scalar().unwrap().iter()) { v.position[1] = *p as f32 },
-            "z" => for (v, p) in verts.iter_mut().zip(p.data().float_scalar().unwrap().iter()) { v.position[2] = *p as f32 },
-            "nx" => for (v, p) in verts.iter_mut().zip(p.data().float_scalar().unwrap().iter()) { v.normal[0] = *p as f32 },
-            "ny" => for (v, p) in verts.iter_mut().zip(p.data().float_scalar().unwrap().iter()) { v.normal[1] = *p as f32 },
-            "nz" => for (v, p) in verts.iter_mut().zip(p.data().float_scalar().unwrap().iter()) { v.normal[2] = *p as f32 },
-            "red"   => for (v, p) in verts.iter_mut().zip(p.data().int_scalar().unwrap().iter()) { v.color[0] = *p as f32 },
-            "green" => for (v, p) in verts.iter_mut().zip(p.data().int_scalar().unwrap().iter()) { v.color[1] = *p as f32 },
-            "blue"  => for (v, p) in verts.iter_mut().zip(p.data().int_scalar().unwrap().iter()) { v.color[2] = *p as f32 },
-            "alpha" => for (v, p) in verts.iter_mut().zip(p.data().int_scalar().unwrap().iter()) { v.color[3] = *p as f32 },
+            "x" => for (v, p) in verts.iter_mut().zip(ply_floats(p)?) { v.position[0] = *p as f32 },
+            "y" => for (v, p) in verts.iter_mut().zip(ply_floats(p)?) { v.position[1] = *p as f32 },
+            "z" => for (v, p) in verts.iter_mut().zip(ply_floats(p)?) { v.position[2] = *p as f32 },
+            "nx" => for (v, p) in verts.iter_mut().zip(ply_floats(p)?) { v.normal[0] = *p as f32 },
+            "ny" => for (v, p) in verts.iter_mut().zip(ply_floats(p)?) { v.normal[1] = *p as f32 },
+            "nz" => for (v, p) in verts.iter_mut().zip(ply_floats(p)?) { v.normal[2] = *p as f32 },
+            "red"   => for (v, p) in verts.iter_mut().zip(ply_ints(p)?) { v.color[0] = *p as f32 },
+            "green" => for (v, p) in verts.iter_mut().zip(ply_ints(p)?) { v.color[1] = *p as f32 },
+            "blue"  => for (v, p) in verts.iter_mut().zip(ply_ints(p)?) { v.color[2] = *p as f32 },
+            "alpha" => for (v, p) in verts.iter_mut().zip(ply_ints(p)?) { v.color[3] = *p as f32 },
+            "s" | "u" | "texture_u" => for (v, p) in verts.iter_mut().zip(ply_numbers(p)?) { v.tex_coords[0] = p },
+            "t" | "v" | "texture_v" => for (v, p) in verts.iter_mut().zip(ply_numbers(p)?) { v.tex_coords[1] = p },
             _ => {},
         }
     }
 
-    // Copy the vertex elements.
-    let faces = doc.elements().iter().find(|e| e.name() == "face").unwrap();
+    // Copy the vertex elements. Files without faces (e.g. raw scanner
+    // output) are point clouds, and every vertex is drawn as a point.
+    let (draw_type, elems) = match ply_element(doc, "face") {
+        Some(faces) => {
+            let indices = faces
+                .properties()
+                .iter()
+                .find(|p| p.name() == "vertex_indices")
+                .and_then(|p| p.data().int_list())
+                .ok_or_else(|| io::Error::other("PLY faces have no vertex_indices list"))?;
+            let mut elems = vec![];
+            for face in indices.iter() {
+                for &j in face.iter() {
+                    if j < 0 || j as usize >= verts.len() {
+                        return Err(io::Error::other(format!(
+                            "PLY face vertex index {} is out of range",
+                            j
+                        )));
+                    }
+                    elems.push(j as u32);
+                }
+            }
+            (PrimitiveType::TrianglesList, elems)
+        }
+        None => (PrimitiveType::Points, (0..verts.len() as u32).collect()),
+    };
+
+    // Postprocessing.
+    center_and_scale(&mut verts);
+
+    // Set the colors, assuming all vertices are opaque.
+    for v in verts.iter_mut() {
+        v.color[0] = (v.color[0] / v.color[3]) * v.position[0].abs();
+        v.color[1] = (v.color[1] / v.color[3]) * v.position[1].abs();
+        v.color[2] = (v.color[2] / v.color[3]) * v.position[2].abs();
+        v.color[3] = 1.0;
+    }
+
+    Ok((draw_type, verts, elems))
+}
+
+pub fn load_obj<F: Facade>(facade: &F, filename: &str) -> io::Result<Geometry<PCNTVertex, u32>> {
+    let data = cached_obj_data(filename)?;
+    Ok(Geometry::with_bounds(facade, data.draw_type, data.vertices, data.indices))
+}
+
+pub fn load_normal_mapped_obj<F: Facade>(
+    facade: &F,
+    filename: &str,
+) -> io::Result<Geometry<PCNTTVertex, u32>> {
+    let data = cached_obj_data(filename)?;
+    let (verts, elems) = with_tangents(&data.vertices, &data.indices);
+    Ok(Geometry::with_bounds(facade, data.draw_type, verts, elems))
+}
+
+fn cached_obj_data(filename: &str) -> io::Result<CachedGeometry> {
+    cache::load_cached(filename, "obj", |source| {
+        let doc = obj::Document::from_reader(source)?;
+        let (vertices, indices) = obj_geometry_data(&doc);
+        Ok(CachedGeometry {
+            draw_type: PrimitiveType::TrianglesList,
+            vertices,
+            indices,
+            scalars: HashMap::new(),
+        })
+    })
+}
+
+fn obj_geometry_data(doc: &obj::Document) -> (Vec<PCNTVertex>, Vec<u32>) {
+    let mut verts: Vec<PCNTVertex> = vec![];
     let mut elems = vec![];
-    for i in 0..(faces.count() as usize) {
-        let p = faces
-            .properties()
-            .iter()
-            .find(|p| p.name() == "vertex_indices")
-            .unwrap();
-        elems.extend(p.data().int_list().unwrap()[i].iter().map(|j| *j as u32));
+
+    // Obj faces index positions, texture coordinates and normals
+    // separately, so each distinct combination becomes a vertex.
+    let mut vert_indices: HashMap<obj::FaceVertex, u32> = HashMap::new();
+    for face in doc.faces() {
+        let mut face_elems = Vec::with_capacity(face.len());
+        for fv in face.iter() {
+            let index = *vert_indices.entry(*fv).or_insert_with(|| {
+                verts.push(PCNTVertex {
+                    position: doc.positions()[fv.position],
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    normal: fv.normal.map(|n| doc.normals()[n]).unwrap_or_default(),
+                    tex_coords: fv.tex_coord.map(|t| doc.tex_coords()[t]).unwrap_or_default(),
+                });
+                (verts.len() - 1) as u32
+            });
+            face_elems.push(index);
+        }
+
+        // Triangulate polygons as fans.
+        for i in 1..(face_elems.len() - 1) {
+            elems.extend_from_slice(&[face_elems[0], face_elems[i], face_elems[i + 1]]);
+        }
+    }
+
+    // Vertices without normals in the file get the area-weighted
+    // average of the normals of the faces around them.
+    if doc.faces().iter().flatten().any(|fv| fv.normal.is_none()) {
+        let mut normals = vec![Vector3::zeros(); verts.len()];
+        for tri in elems.chunks(3) {
+            let (i1, i2, i3) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+            let e1 = verts[i2].position_vec() - verts[i1].position_vec();
+            let e2 = verts[i3].position_vec() - verts[i1].position_vec();
+            let face_normal = e1.cross(&e2);
+            for &i in &[i1, i2, i3] {
+                normals[i] += face_normal;
+            }
+        }
+
+        for (v, n) in verts.iter_mut().zip(normals.iter()) {
+            if v.normal == [0.0, 0.0, 0.0] && n.norm() > 0.0 {
+                v.normal = *n.normalize().as_ref();
+            }
+        }
     }
 
-    // Postprocessing. Calculate the bounding box.
+    center_and_scale(&mut verts);
+    (verts, elems)
+}
+
+/// Scales the positions so that they're in the range -1 to 1 and
+/// centered on the origin. If they're all the same, e.g. a point cloud
+/// with only one point, they're only centered.
+fn center_and_scale(verts: &mut [PCNTVertex]) {
+    // Calculate the bounding box.
     let mut bb_min = Vector3::max_value();
     let mut bb_max = Vector3::min_value();
     for v in verts.iter() {
@@ -198,21 +618,147 @@ pub fn load_ply<F: Facade>(facade: &F, filename: &str) -> Geometry<PCNVertex, u3
         bb_max = bb_max.sup(&v.position_vec());
     }
 
-    // Scale everything so that it's in the range -1 to 1 and centered.
     let bcenter = (bb_max + bb_min) / 2.0;
     let new_bb_max = bb_max - bcenter;
-    let max_dim = new_bb_max.iter().fold(f32::min_value(), |m, &v| m.max(v));
+    let mut max_dim = new_bb_max.iter().fold(f32::min_value(), |m, &v| m.max(v));
+    if max_dim <= 0.0 {
+        max_dim = 1.0;
+    }
     for v in verts.iter_mut() {
         v.position = *((v.position_vec() - bcenter) / max_dim).as_ref();
     }
+}
 
-    // Set the colors, assuming all vertices are opaque.
-    for v in verts.iter_mut() {
-        v.color[0] = (v.color[0] / v.color[3]) * v.position[0].abs();
-        v.color[1] = (v.color[1] / v.color[3]) * v.position[1].abs();
-        v.color[2] = (v.color[2] / v.color[3]) * v.position[2].abs();
-        v.color[3] = 1.0;
+#[cfg(test)]
+mod tests {
+    use glium::index::PrimitiveType;
+
+    use super::{
+        cached_obj_data, obj_geometry_data, ply_geometry_data, ply_vertex_scalars, read_ply,
+    };
+    use crate::{obj, ply::Document};
+
+    static POINT_CLOUD: &str = r"ply
+format ascii 1.0
+element vertex 4
+property float32 x
+property float32 y
+property float32 z
+end_header
+1.0 0.0 0.0
+-1.0 0.0 0.0
+0.0 2.0 0.0
+0.0 0.0 -2.0
+";
+
+    #[test]
+    fn point_cloud_without_faces() {
+        let doc = Document::from_reader(POINT_CLOUD.as_bytes()).unwrap();
+        let (draw_type, verts, elems) = ply_geometry_data(&doc).unwrap();
+
+        assert_eq!(PrimitiveType::Points, draw_type);
+        assert_eq!(vec![0, 1, 2, 3], elems);
+        assert_eq!(4, verts.len());
+
+        // Normalized to fit in -1 to 1, with opaque colors.
+        assert_eq!([1.0, -1.0, 1.0], verts[0].position);
+        assert_eq!([0.0, 1.0, 1.0], verts[2].position);
+        for v in verts.iter() {
+            assert!(v.color.iter().all(|c| c.is_finite()));
+            assert_eq!(1.0, v.color[3]);
+        }
+    }
+
+    #[test]
+    fn single_point_cloud() {
+        let doc = Document::from_reader(
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n3 4 5\n3 4 5\n".as_bytes(),
+        )
+        .unwrap();
+        let (_, verts, _) = ply_geometry_data(&doc).unwrap();
+        for v in verts.iter() {
+            assert_eq!([0.0, 0.0, 0.0], v.position);
+            assert!(v.color.iter().all(|c| c.is_finite()));
+        }
+    }
+
+    #[test]
+    fn ply_extra_scalars() {
+        let doc = Document::from_reader(
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nproperty float quality\nproperty uchar label\nend_header\n0 0 0 0.25 3\n1 1 1 0.5 7\n".as_bytes(),
+        )
+        .unwrap();
+        let scalars = ply_vertex_scalars(&doc).unwrap();
+        assert_eq!(2, scalars.len());
+        assert_eq!(vec![0.25, 0.5], scalars["quality"]);
+        assert_eq!(vec![3.0, 7.0], scalars["label"]);
+    }
+
+    #[test]
+    fn ply_texture_coordinates() {
+        let doc = Document::from_reader(
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nproperty float s\nproperty float t\nend_header\n0 0 0 0.25 0.5\n1 1 1 0.75 1.0\n".as_bytes(),
+        )
+        .unwrap();
+        let (_, verts, _) = ply_geometry_data(&doc).unwrap();
+        assert_eq!([0.25, 0.5], verts[0].tex_coords);
+        assert_eq!([0.75, 1.0], verts[1].tex_coords);
+
+        // Some exporters store texel coordinates as integers.
+        let doc = Document::from_reader(
+            "ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nproperty ushort u\nproperty ushort v\nend_header\n0 0 0 0 1\n1 1 1 1 0\n".as_bytes(),
+        )
+        .unwrap();
+        let (_, verts, _) = ply_geometry_data(&doc).unwrap();
+        assert_eq!([0.0, 1.0], verts[0].tex_coords);
+        assert_eq!([1.0, 0.0], verts[1].tex_coords);
+    }
+
+    #[test]
+    fn obj_welds_and_triangulates() {
+        let doc = obj::Document::from_reader(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf 1/1 2/2 3/3 4/4\nf 1/1 3/3 4/4\n".as_bytes(),
+        )
+        .unwrap();
+        let (verts, elems) = obj_geometry_data(&doc);
+
+        assert_eq!(4, verts.len());
+        assert_eq!(vec![0, 1, 2, 0, 2, 3, 0, 2, 3], elems);
+        assert_eq!([1.0, 1.0], verts[2].tex_coords);
+
+        // No normals in the file, so they're computed from the faces.
+        for v in verts.iter() {
+            assert_eq!([0.0, 0.0, 1.0], v.normal);
+        }
+    }
+
+    #[test]
+    fn malformed_ply_is_an_error() {
+        let header = "ply\nformat ascii 1.0\n";
+        let bad = [
+            // No vertices.
+            "element face 0\nproperty list uchar int vertex_indices\nend_header\n",
+            // Integer positions.
+            "element vertex 1\nproperty int x\nend_header\n1\n",
+            // Float colors.
+            "element vertex 1\nproperty float red\nend_header\n0.5\n",
+            // Faces without indices.
+            "element vertex 1\nproperty float x\nelement face 1\nproperty int n\nend_header\n0\n3\n",
+            // Faces using vertices which aren't there.
+            "element vertex 1\nproperty float x\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0\n3 0 1 2\n",
+        ];
+        for body in bad.iter() {
+            let doc = Document::from_reader(format!("{}{}", header, body).as_bytes()).unwrap();
+            assert!(ply_geometry_data(&doc).is_err(), "{}", body);
+            if body == &bad[0] {
+                assert!(ply_vertex_scalars(&doc).is_err());
+            }
+        }
     }
 
-    Geometry::new(facade, PrimitiveType::TrianglesList, verts, elems)
+    #[test]
+    fn missing_files_are_errors() {
+        assert!(read_ply("nowhere.ply").is_err());
+        assert!(cached_obj_data("nowhere.obj").is_err());
+    }
 }
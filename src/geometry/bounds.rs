@@ -0,0 +1,171 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use super::{PCNTTVertex, PCNTVertex, PCNVertex};
+
+/// Vertex types with a position, which geometry can be bounded by.
+pub trait HasPosition {
+    fn position(&self) -> [f32; 3];
+}
+
+impl HasPosition for PCNVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
+impl HasPosition for PCNTVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
+impl HasPosition for PCNTTVertex {
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// A sphere around the given vertices, centered on their bounding
+    /// box. It isn't the smallest such sphere, but it's close enough
+    /// for culling. Returns `None` if there aren't any vertices.
+    pub fn from_vertices<V: HasPosition>(verts: &[V]) -> Option<BoundingSphere> {
+        if verts.is_empty() {
+            return None;
+        }
+
+        let mut min = Vector3::repeat(f32::INFINITY);
+        let mut max = Vector3::repeat(f32::NEG_INFINITY);
+        for v in verts.iter() {
+            let p = Vector3::from(v.position());
+            min = min.inf(&p);
+            max = max.sup(&p);
+        }
+
+        let center = (min + max) / 2.0;
+        let radius = verts
+            .iter()
+            .map(|v| (Vector3::from(v.position()) - center).norm())
+            .fold(0.0, f32::max);
+        Some(BoundingSphere {
+            center: Point3::from(center),
+            radius,
+        })
+    }
+
+    /// A sphere around this one after it's transformed. Non-uniform
+    /// scaling or shear can make it larger than it needs to be.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> BoundingSphere {
+        // The most the transform stretches any vector is the square
+        // root of the largest eigenvalue of MᵀM, which is at most its
+        // largest absolute row sum.
+        let linear = transform.fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0);
+        let max_scale_squared = (linear.transpose() * linear)
+            .abs()
+            .row_iter()
+            .map(|row| row.sum())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: transform.transform_point(&self.center),
+            radius: self.radius * max_scale_squared.sqrt(),
+        }
+    }
+
+    /// The smallest sphere around both this one and another.
+    pub fn merged(&self, other: &BoundingSphere) -> BoundingSphere {
+        let offset = other.center - self.center;
+        let distance = offset.norm();
+        if distance + other.radius <= self.radius {
+            *self
+        } else if distance + self.radius <= other.radius {
+            *other
+        } else {
+            let radius = (distance + self.radius + other.radius) / 2.0;
+            BoundingSphere {
+                center: self.center + offset * ((radius - self.radius) / distance),
+                radius,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Point3, Vector3};
+
+    use super::BoundingSphere;
+    use crate::geometry::PCNVertex;
+
+    #[test]
+    fn sphere_around_vertices() {
+        let verts: Vec<PCNVertex> = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [1.0, 1.0, 0.0]]
+            .iter()
+            .map(|p| PCNVertex {
+                position: *p,
+                ..Default::default()
+            })
+            .collect();
+        let sphere = BoundingSphere::from_vertices(&verts).unwrap();
+        assert_eq!(Point3::new(1.0, 0.5, 0.0), sphere.center);
+        assert!((sphere.radius - 1.25f32.sqrt()).abs() < 1e-6);
+
+        assert_eq!(None, BoundingSphere::from_vertices::<PCNVertex>(&[]));
+    }
+
+    #[test]
+    fn transformed_sphere() {
+        let sphere = BoundingSphere {
+            center: Point3::new(1.0, 0.0, 0.0),
+            radius: 1.0,
+        };
+        let transform = Matrix4::new_translation(&Vector3::new(0.0, 5.0, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 3.0, 2.0));
+        let transformed = sphere.transformed(&transform);
+        assert_eq!(Point3::new(1.0, 5.0, 0.0), transformed.center);
+        assert_eq!(3.0, transformed.radius);
+    }
+
+    #[test]
+    fn sheared_sphere() {
+        // Shearing stretches some directions by more than any of the
+        // axes: here, by the golden ratio.
+        let sphere = BoundingSphere {
+            center: Point3::origin(),
+            radius: 1.0,
+        };
+        let mut transform = Matrix4::identity();
+        transform[(0, 1)] = 1.0;
+        let transformed = sphere.transformed(&transform);
+        let stretched = transform.transform_vector(&Vector3::new(1.0, 1.618034, 0.0).normalize());
+        assert!(stretched.norm() > 1.618);
+        assert!(transformed.radius >= stretched.norm());
+    }
+
+    #[test]
+    fn merged_spheres() {
+        let a = BoundingSphere {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+        };
+        let b = BoundingSphere {
+            center: Point3::new(4.0, 0.0, 0.0),
+            radius: 2.0,
+        };
+        let merged = a.merged(&b);
+        assert_eq!(Point3::new(2.5, 0.0, 0.0), merged.center);
+        assert_eq!(3.5, merged.radius);
+
+        let inside = BoundingSphere {
+            center: Point3::new(4.5, 0.0, 0.0),
+            radius: 0.5,
+        };
+        assert_eq!(b, b.merged(&inside));
+        assert_eq!(b, inside.merged(&b));
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use glium::index::PrimitiveType;
+
+use super::PCNTVertex;
+
+/// Loaded and processed geometry, as stored in a cache file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedGeometry {
+    pub draw_type: PrimitiveType,
+    pub vertices: Vec<PCNTVertex>,
+    pub indices: Vec<u32>,
+    pub scalars: HashMap<String, Vec<f32>>,
+}
+
+static MAGIC: &[u8; 8] = b"GP2GEOM\0";
+
+/// Bump this whenever the file layout or the processing done by any
+/// of the loaders changes, to invalidate existing caches.
+pub const VERSION: u32 = 1;
+
+/// The extension added to a source file's name to get its cache's.
+pub const CACHE_EXTENSION: &str = "cache";
+
+/// Identifies the cached data for a source file processed with the
+/// given options: a 64-bit FNV-1a hash of the format version, the
+/// options and the file's contents.
+pub fn cache_key(source: &[u8], options: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let version = VERSION.to_le_bytes();
+    let bytes = version
+        .iter()
+        .chain(options.as_bytes())
+        .chain([0].iter())
+        .chain(source);
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn cache_path(filename: &str) -> PathBuf {
+    PathBuf::from(format!("{}.{}", filename, CACHE_EXTENSION))
+}
+
+/// Reads `filename` and returns its processed geometry, from its cache
+/// file if there's one for the same contents and options, and from
+/// `process` otherwise. A missing, stale or unreadable cache is
+/// silently rewritten; failing to write it isn't an error either.
+pub fn load_cached<P>(filename: &str, options: &str, process: P) -> io::Result<CachedGeometry>
+where
+    P: FnOnce(&[u8]) -> io::Result<CachedGeometry>,
+{
+    let source = fs::read(filename)?;
+    let key = cache_key(&source, options);
+    let path = cache_path(filename);
+
+    if let Ok(file) = File::open(&path) {
+        if let Ok(Some(cached)) = read_cache(BufReader::new(file), key) {
+            return Ok(cached);
+        }
+    }
+
+    let geometry = process(&source)?;
+    if let Ok(file) = File::create(&path) {
+        let mut writer = BufWriter::new(file);
+        if write_cache(&mut writer, key, &geometry)
+            .and_then(|_| writer.flush())
+            .is_err()
+        {
+            let _ = fs::remove_file(&path);
+        }
+    }
+    Ok(geometry)
+}
+
+pub fn write_cache<W: Write>(mut writer: W, key: u64, geometry: &CachedGeometry) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(VERSION)?;
+    writer.write_u64::<LittleEndian>(key)?;
+    writer.write_u8(draw_type_code(geometry.draw_type)?)?;
+
+    writer.write_u32::<LittleEndian>(geometry.vertices.len() as u32)?;
+    for v in geometry.vertices.iter() {
+        let attributes = v
+            .position
+            .iter()
+            .chain(v.color.iter())
+            .chain(v.normal.iter())
+            .chain(v.tex_coords.iter());
+        for value in attributes {
+            writer.write_f32::<LittleEndian>(*value)?;
+        }
+    }
+
+    writer.write_u32::<LittleEndian>(geometry.indices.len() as u32)?;
+    for i in geometry.indices.iter() {
+        writer.write_u32::<LittleEndian>(*i)?;
+    }
+
+    // Sorted, so that the same data always writes the same file.
+    let mut names: Vec<&String> = geometry.scalars.keys().collect();
+    names.sort();
+    writer.write_u32::<LittleEndian>(names.len() as u32)?;
+    for name in names {
+        let values = &geometry.scalars[name];
+        writer.write_u32::<LittleEndian>(name.len() as u32)?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_u32::<LittleEndian>(values.len() as u32)?;
+        for value in values.iter() {
+            writer.write_f32::<LittleEndian>(*value)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a cache file, returning `None` if it's from a different
+/// version or for a different key.
+pub fn read_cache<R: Read>(mut reader: R, key: u64) -> io::Result<Option<CachedGeometry>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(other_io_error("Not a geometry cache file"));
+    }
+    if reader.read_u32::<LittleEndian>()? != VERSION || reader.read_u64::<LittleEndian>()? != key {
+        return Ok(None);
+    }
+    let draw_type = draw_type_from_code(reader.read_u8()?)?;
+
+    let vertex_count = reader.read_u32::<LittleEndian>()? as usize;
+    let mut vertices = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        let mut attributes = [0.0; 12];
+        reader.read_f32_into::<LittleEndian>(&mut attributes)?;
+        let a = attributes;
+        vertices.push(PCNTVertex {
+            position: [a[0], a[1], a[2]],
+            color: [a[3], a[4], a[5], a[6]],
+            normal: [a[7], a[8], a[9]],
+            tex_coords: [a[10], a[11]],
+        });
+    }
+
+    let index_count = reader.read_u32::<LittleEndian>()? as usize;
+    let mut indices = vec![0; index_count];
+    reader.read_u32_into::<LittleEndian>(&mut indices)?;
+
+    let scalar_count = reader.read_u32::<LittleEndian>()?;
+    let mut scalars = HashMap::new();
+    for _ in 0..scalar_count {
+        let mut name = vec![0; reader.read_u32::<LittleEndian>()? as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name)
+            .map_err(|_| other_io_error("Invalid scalar name in geometry cache"))?;
+        let mut values = vec![0.0; reader.read_u32::<LittleEndian>()? as usize];
+        reader.read_f32_into::<LittleEndian>(&mut values)?;
+        scalars.insert(name, values);
+    }
+
+    Ok(Some(CachedGeometry {
+        draw_type,
+        vertices,
+        indices,
+        scalars,
+    }))
+}
+
+static DRAW_TYPES: [PrimitiveType; 6] = [
+    PrimitiveType::Points,
+    PrimitiveType::LinesList,
+    PrimitiveType::LineStrip,
+    PrimitiveType::TrianglesList,
+    PrimitiveType::TriangleStrip,
+    PrimitiveType::TriangleFan,
+];
+
+fn draw_type_code(draw_type: PrimitiveType) -> io::Result<u8> {
+    DRAW_TYPES
+        .iter()
+        .position(|t| *t == draw_type)
+        .map(|i| i as u8)
+        .ok_or_else(|| other_io_error(&format!("Can't cache geometry drawn as {:?}", draw_type)))
+}
+
+fn draw_type_from_code(code: u8) -> io::Result<PrimitiveType> {
+    DRAW_TYPES
+        .get(code as usize)
+        .cloned()
+        .ok_or_else(|| other_io_error("Unknown draw type in geometry cache"))
+}
+
+fn other_io_error(msg: &str) -> io::Error {
+    io::Error::other(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, collections::HashMap, fs, io};
+
+    use glium::index::PrimitiveType;
+
+    use super::{cache_key, cache_path, load_cached, read_cache, write_cache, CachedGeometry};
+    use crate::geometry::PCNTVertex;
+
+    fn geometry() -> CachedGeometry {
+        let mut scalars = HashMap::new();
+        scalars.insert("quality".to_string(), vec![0.25, 0.5]);
+        CachedGeometry {
+            draw_type: PrimitiveType::LinesList,
+            vertices: vec![
+                PCNTVertex {
+                    position: [1.0, 2.0, 3.0],
+                    color: [0.1, 0.2, 0.3, 1.0],
+                    normal: [0.0, 0.0, 1.0],
+                    tex_coords: [0.5, 0.75],
+                },
+                PCNTVertex::default(),
+            ],
+            indices: vec![0, 1],
+            scalars,
+        }
+    }
+
+    #[test]
+    fn keys_depend_on_source_and_options() {
+        let key = cache_key(b"v 0 0 0", "obj");
+        assert_eq!(key, cache_key(b"v 0 0 0", "obj"));
+        assert_ne!(key, cache_key(b"v 0 0 1", "obj"));
+        assert_ne!(key, cache_key(b"v 0 0 0", "ply"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bytes = vec![];
+        write_cache(&mut bytes, 42, &geometry()).unwrap();
+        assert_eq!(Some(geometry()), read_cache(&bytes[..], 42).unwrap());
+        assert_eq!(None, read_cache(&bytes[..], 43).unwrap());
+        assert!(read_cache(&bytes[1..], 42).is_err());
+        assert!(read_cache(&bytes[..bytes.len() - 1], 42).is_err());
+    }
+
+    #[test]
+    fn invalidated_when_source_changes() {
+        let source =
+            std::env::temp_dir().join(format!("graphplay2-cache-{}.txt", std::process::id()));
+        let source = source.to_str().unwrap();
+        let processed = Cell::new(0);
+        let process = |_: &[u8]| -> io::Result<CachedGeometry> {
+            processed.set(processed.get() + 1);
+            Ok(geometry())
+        };
+
+        fs::write(source, "one").unwrap();
+        assert_eq!(geometry(), load_cached(source, "test", process).unwrap());
+        assert_eq!(geometry(), load_cached(source, "test", process).unwrap());
+        assert_eq!(1, processed.get());
+
+        fs::write(source, "two").unwrap();
+        load_cached(source, "test", process).unwrap();
+        assert_eq!(2, processed.get());
+
+        fs::remove_file(source).unwrap();
+        fs::remove_file(cache_path(source)).unwrap();
+    }
+}
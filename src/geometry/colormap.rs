@@ -0,0 +1,191 @@
+use super::PCNVertex;
+
+/// A mapping from scalars in [0, 1] to colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Colormap {
+    /// Perceptually uniform dark purple to yellow.
+    Viridis,
+    /// The classic (not perceptually uniform) blue to red rainbow.
+    Jet,
+    /// Blue to white to red, for values with a meaningful midpoint
+    /// such as signed curvature.
+    Diverging,
+    /// Water, sand, grass, rock and snow, for heights.
+    Terrain,
+}
+
+/// The scalar values which map to the ends of a colormap. Values
+/// outside the range are clamped to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalarRange {
+    /// The minimum and maximum of the values.
+    Auto,
+    /// A fixed minimum and maximum.
+    Fixed(f32, f32),
+    /// The given lower and upper percentiles (0 to 100) of the values,
+    /// which is more useful than `Auto` for values with outliers.
+    Percentile(f32, f32),
+    /// Symmetric about zero, out to the largest absolute value. Meant
+    /// for use with `Colormap::Diverging`.
+    Symmetric,
+}
+
+// Samples of matplotlib's viridis at even intervals.
+static VIRIDIS: [[f32; 3]; 10] = [
+    [0.267, 0.004, 0.329],
+    [0.282, 0.157, 0.471],
+    [0.243, 0.290, 0.537],
+    [0.192, 0.408, 0.557],
+    [0.149, 0.510, 0.557],
+    [0.122, 0.620, 0.537],
+    [0.208, 0.718, 0.475],
+    [0.427, 0.804, 0.349],
+    [0.706, 0.871, 0.173],
+    [0.992, 0.906, 0.145],
+];
+
+// Moreland's "cool to warm" diverging map.
+static DIVERGING: [[f32; 3]; 5] = [
+    [0.230, 0.299, 0.754],
+    [0.552, 0.690, 0.996],
+    [0.865, 0.865, 0.865],
+    [0.958, 0.604, 0.483],
+    [0.706, 0.016, 0.150],
+];
+
+static TERRAIN: [[f32; 3]; 6] = [
+    [0.10, 0.25, 0.55],
+    [0.25, 0.55, 0.80],
+    [0.85, 0.80, 0.55],
+    [0.30, 0.60, 0.25],
+    [0.45, 0.40, 0.35],
+    [0.95, 0.95, 0.95],
+];
+
+impl Colormap {
+    /// The color for `t`, which is clamped to [0, 1].
+    pub fn color(&self, t: f32) -> [f32; 4] {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let [r, g, b] = match *self {
+            Colormap::Viridis => interpolate(&VIRIDIS, t),
+            Colormap::Jet => [
+                (1.5 - (4.0 * t - 3.0).abs()).clamp(0.0, 1.0),
+                (1.5 - (4.0 * t - 2.0).abs()).clamp(0.0, 1.0),
+                (1.5 - (4.0 * t - 1.0).abs()).clamp(0.0, 1.0),
+            ],
+            Colormap::Diverging => interpolate(&DIVERGING, t),
+            Colormap::Terrain => interpolate(&TERRAIN, t),
+        };
+        [r, g, b, 1.0]
+    }
+}
+
+impl ScalarRange {
+    /// The (minimum, maximum) of this range for the given values.
+    pub fn bounds(&self, values: &[f32]) -> (f32, f32) {
+        let finite = || values.iter().cloned().filter(|v| v.is_finite());
+        match *self {
+            ScalarRange::Auto => (
+                finite().fold(f32::INFINITY, f32::min),
+                finite().fold(f32::NEG_INFINITY, f32::max),
+            ),
+            ScalarRange::Fixed(min, max) => (min, max),
+            ScalarRange::Percentile(low, high) => {
+                let mut sorted: Vec<f32> = finite().collect();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                (percentile(&sorted, low), percentile(&sorted, high))
+            }
+            ScalarRange::Symmetric => {
+                let max = finite().fold(0.0, |m: f32, v| m.max(v.abs()));
+                (-max, max)
+            }
+        }
+    }
+}
+
+/// Maps each scalar value through the colormap, and writes the
+/// result into the color of the corresponding vertex.
+pub fn apply_colormap(
+    verts: &mut [PCNVertex],
+    values: &[f32],
+    colormap: Colormap,
+    range: ScalarRange,
+) {
+    let (min, max) = range.bounds(values);
+    let span = if max > min { max - min } else { 1.0 };
+    for (v, value) in verts.iter_mut().zip(values.iter()) {
+        v.color = colormap.color((value - min) / span);
+    }
+}
+
+fn interpolate(table: &[[f32; 3]], t: f32) -> [f32; 3] {
+    let scaled = t * (table.len() - 1) as f32;
+    let i = (scaled.floor() as usize).min(table.len() - 2);
+    let frac = scaled - i as f32;
+    let (c0, c1) = (table[i], table[i + 1]);
+    [
+        c0[0] + (c1[0] - c0[0]) * frac,
+        c0[1] + (c1[1] - c0[1]) * frac,
+        c0[2] + (c1[2] - c0[2]) * frac,
+    ]
+}
+
+fn percentile(sorted: &[f32], pct: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = (pct.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f32).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_colormap, Colormap, ScalarRange, DIVERGING, VIRIDIS};
+    use crate::geometry::PCNVertex;
+
+    #[test]
+    fn colormap_ends() {
+        let [r, g, b] = VIRIDIS[0];
+        assert_eq!([r, g, b, 1.0], Colormap::Viridis.color(0.0));
+        let [r, g, b] = VIRIDIS[9];
+        assert_eq!([r, g, b, 1.0], Colormap::Viridis.color(1.0));
+
+        assert_eq!([0.0, 0.0, 0.5, 1.0], Colormap::Jet.color(0.0));
+        assert_eq!([0.5, 0.0, 0.0, 1.0], Colormap::Jet.color(1.0));
+
+        let [r, g, b] = DIVERGING[2];
+        assert_eq!([r, g, b, 1.0], Colormap::Diverging.color(0.5));
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_eq!(Colormap::Jet.color(0.0), Colormap::Jet.color(-3.0));
+        assert_eq!(Colormap::Jet.color(1.0), Colormap::Jet.color(7.0));
+    }
+
+    #[test]
+    fn range_bounds() {
+        let values = [3.0, -1.0, 0.0, 2.0, f32::NAN, 100.0];
+        assert_eq!((-1.0, 100.0), ScalarRange::Auto.bounds(&values));
+        assert_eq!((0.0, 1.0), ScalarRange::Fixed(0.0, 1.0).bounds(&values));
+        assert_eq!((-100.0, 100.0), ScalarRange::Symmetric.bounds(&values));
+        assert_eq!(
+            (-1.0, 3.0),
+            ScalarRange::Percentile(0.0, 75.0).bounds(&values)
+        );
+    }
+
+    #[test]
+    fn writes_vertex_colors() {
+        let mut verts = vec![PCNVertex::default(); 3];
+        apply_colormap(
+            &mut verts,
+            &[1.0, 2.0, 3.0],
+            Colormap::Diverging,
+            ScalarRange::Auto,
+        );
+        assert_eq!(Colormap::Diverging.color(0.0), verts[0].color);
+        assert_eq!(Colormap::Diverging.color(0.5), verts[1].color);
+        assert_eq!(Colormap::Diverging.color(1.0), verts[2].color);
+    }
+}
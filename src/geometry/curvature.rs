@@ -0,0 +1,219 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use nalgebra::Vector3;
+
+use super::PCNVertex;
+
+/// Per-vertex Gaussian curvature of a triangle mesh: the angle
+/// deficit around each vertex divided by its share (a third) of the
+/// area of the triangles around it. Boundary vertices, where the
+/// deficit measures how the boundary curves rather than the surface,
+/// get 0.
+pub fn gaussian_curvature(verts: &[PCNVertex], elems: &[u32]) -> Vec<f32> {
+    let positions = positions(verts);
+    let areas = vertex_areas(&positions, elems);
+    let boundary = boundary_vertices(positions.len(), elems);
+
+    let mut angle_sums = vec![0.0; positions.len()];
+    for tri in elems.chunks_exact(3) {
+        for corner in 0..3 {
+            let i = tri[corner] as usize;
+            let a = positions[tri[(corner + 1) % 3] as usize] - positions[i];
+            let b = positions[tri[(corner + 2) % 3] as usize] - positions[i];
+            angle_sums[i] += a.angle(&b);
+        }
+    }
+
+    angle_sums
+        .iter()
+        .zip(areas.iter().zip(boundary.iter()))
+        .map(|(angle_sum, (area, on_boundary))| {
+            if *area > 0.0 && !on_boundary {
+                (2.0 * PI - angle_sum) / area
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Per-vertex mean curvature of a triangle mesh, from the cotangent
+/// Laplacian. It's positive where the surface is convex, assuming the
+/// triangles are wound counterclockwise when seen from outside.
+pub fn mean_curvature(verts: &[PCNVertex], elems: &[u32]) -> Vec<f32> {
+    let positions = positions(verts);
+    let areas = vertex_areas(&positions, elems);
+    let normals = area_weighted_normals(&positions, elems);
+
+    let mut laplacian = vec![Vector3::zeros(); positions.len()];
+    for (&(i, j), weight) in cotangent_weights(&positions, elems).iter() {
+        let (i, j) = (i as usize, j as usize);
+        let edge = positions[j] - positions[i];
+        laplacian[i] += edge * *weight;
+        laplacian[j] -= edge * *weight;
+    }
+
+    // The Laplace-Beltrami operator applied to the positions is -2Hn.
+    laplacian
+        .iter()
+        .zip(areas.iter().zip(normals.iter()))
+        .map(|(l, (area, normal))| {
+            if *area > 0.0 && normal.norm() > 0.0 {
+                -l.dot(&normal.normalize()) / (2.0 * area)
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn positions(verts: &[PCNVertex]) -> Vec<Vector3<f32>> {
+    verts.iter().map(|v| v.position_vec()).collect()
+}
+
+/// The cotangent weight, (cot α + cot β) / 2, of each edge, keyed by
+/// its vertex indices with the lower index first. α and β are the
+/// angles opposite the edge in the (up to two) triangles sharing it.
+pub(crate) fn cotangent_weights(
+    positions: &[Vector3<f32>],
+    elems: &[u32],
+) -> HashMap<(u32, u32), f32> {
+    let mut weights = HashMap::new();
+    for tri in elems.chunks_exact(3) {
+        for corner in 0..3 {
+            let i = tri[(corner + 1) % 3];
+            let j = tri[(corner + 2) % 3];
+            let a = positions[i as usize] - positions[tri[corner] as usize];
+            let b = positions[j as usize] - positions[tri[corner] as usize];
+            let cot = a.dot(&b) / a.cross(&b).norm().max(f32::EPSILON);
+            *weights.entry((i.min(j), i.max(j))).or_insert(0.0) += cot / 2.0;
+        }
+    }
+    weights
+}
+
+/// Whether each vertex lies on an edge used by only one triangle.
+pub(crate) fn boundary_vertices(vertex_count: usize, elems: &[u32]) -> Vec<bool> {
+    let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+    for tri in elems.chunks_exact(3) {
+        for corner in 0..3 {
+            let (i, j) = (tri[corner], tri[(corner + 1) % 3]);
+            *edge_counts.entry((i.min(j), i.max(j))).or_insert(0) += 1;
+        }
+    }
+
+    let mut boundary = vec![false; vertex_count];
+    for (&(i, j), count) in edge_counts.iter() {
+        if *count == 1 {
+            boundary[i as usize] = true;
+            boundary[j as usize] = true;
+        }
+    }
+    boundary
+}
+
+pub(crate) fn area_weighted_normals(
+    positions: &[Vector3<f32>],
+    elems: &[u32],
+) -> Vec<Vector3<f32>> {
+    let mut normals = vec![Vector3::zeros(); positions.len()];
+    for tri in elems.chunks_exact(3) {
+        let (i1, i2, i3) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        let face_normal = (positions[i2] - positions[i1]).cross(&(positions[i3] - positions[i1]));
+        for &i in &[i1, i2, i3] {
+            normals[i] += face_normal;
+        }
+    }
+    normals
+}
+
+fn vertex_areas(positions: &[Vector3<f32>], elems: &[u32]) -> Vec<f32> {
+    let mut areas = vec![0.0; positions.len()];
+    for tri in elems.chunks_exact(3) {
+        let (i1, i2, i3) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        let area = (positions[i2] - positions[i1])
+            .cross(&(positions[i3] - positions[i1]))
+            .norm()
+            / 2.0;
+        for &i in &[i1, i2, i3] {
+            areas[i] += area / 3.0;
+        }
+    }
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::{gaussian_curvature, mean_curvature};
+    use crate::geometry::{octohedron_data, PCNVertex};
+
+    fn vertex(position: [f32; 3]) -> PCNVertex {
+        PCNVertex {
+            position,
+            ..Default::default()
+        }
+    }
+
+    // A 3x3 grid of vertices in the z = height(x, y) surface.
+    fn grid<H: Fn(f32, f32) -> f32>(height: H) -> (Vec<PCNVertex>, Vec<u32>) {
+        let mut verts = vec![];
+        for y in 0..3 {
+            for x in 0..3 {
+                let (x, y) = (x as f32 - 1.0, y as f32 - 1.0);
+                verts.push(vertex([x, y, height(x, y)]));
+            }
+        }
+        let mut elems = vec![];
+        for y in 0..2 {
+            for x in 0..2 {
+                let i = y * 3 + x;
+                elems.extend_from_slice(&[i, i + 1, i + 4, i, i + 4, i + 3]);
+            }
+        }
+        (verts, elems)
+    }
+
+    #[test]
+    fn octohedron_gaussian_curvature() {
+        // Each vertex has four 60 degree angles around it, and a third
+        // of four faces of area sqrt(3) / 2.
+        let (verts, elems) = octohedron_data();
+        let expected = (2.0 * PI / 3.0) / (2.0 * 3.0f32.sqrt() / 3.0);
+        for k in gaussian_curvature(&verts, &elems) {
+            assert!((k - expected).abs() < 1e-5, "{} != {}", k, expected);
+        }
+    }
+
+    #[test]
+    fn octohedron_is_convex() {
+        let (verts, elems) = octohedron_data();
+        for h in mean_curvature(&verts, &elems) {
+            assert!(h > 0.0);
+        }
+    }
+
+    #[test]
+    fn flat_grid_has_no_curvature() {
+        let (verts, elems) = grid(|_, _| 0.0);
+        assert!(gaussian_curvature(&verts, &elems)
+            .iter()
+            .all(|k| k.abs() < 1e-5));
+        assert!(mean_curvature(&verts, &elems)
+            .iter()
+            .all(|h| h.abs() < 1e-5));
+    }
+
+    #[test]
+    fn bump_is_convex_at_its_peak() {
+        // Seen from above (+z, the side the triangles face), a bump up
+        // in the middle is convex and a dent is concave.
+        let (verts, elems) = grid(|x, y| if x == 0.0 && y == 0.0 { 0.5 } else { 0.0 });
+        assert!(mean_curvature(&verts, &elems)[4] > 0.0);
+        assert!(gaussian_curvature(&verts, &elems)[4] > 0.0);
+
+        let (verts, elems) = grid(|x, y| if x == 0.0 && y == 0.0 { -0.5 } else { 0.0 });
+        assert!(mean_curvature(&verts, &elems)[4] < 0.0);
+    }
+}
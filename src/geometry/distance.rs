@@ -0,0 +1,360 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use nalgebra::{Point3, Vector3};
+
+use super::{curvature::positions, PCNVertex, ScalarGrid};
+
+/// Distance queries against a triangle mesh, accelerated by a bounding
+/// volume hierarchy over its triangles.
+///
+/// The sign of the distance comes from angle-weighted pseudo-normals,
+/// so it's only meaningful for closed, consistently-wound meshes.
+#[derive(Clone, Debug)]
+pub struct MeshDistance {
+    positions: Vec<Vector3<f32>>,
+    triangles: Vec<[usize; 3]>,
+    face_normals: Vec<Vector3<f32>>,
+    edge_normals: HashMap<(usize, usize), Vector3<f32>>,
+    vertex_normals: Vec<Vector3<f32>>,
+    nodes: Vec<Node>,
+}
+
+/// The result of a closest point query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestPoint {
+    pub point: Point3<f32>,
+    pub triangle: usize,
+    pub distance: f32,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    contents: NodeContents,
+}
+
+#[derive(Clone, Debug)]
+enum NodeContents {
+    Leaf(Vec<usize>),
+    Branch(usize, usize),
+}
+
+// Which part of a triangle a closest point lies on, by corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Feature {
+    Vertex(usize),
+    Edge(usize, usize),
+    Face,
+}
+
+const LEAF_SIZE: usize = 4;
+
+impl MeshDistance {
+    pub fn new(verts: &[PCNVertex], elems: &[u32]) -> MeshDistance {
+        let positions = positions(verts);
+        let triangles: Vec<[usize; 3]> = elems
+            .chunks_exact(3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+            .collect();
+        assert!(!triangles.is_empty());
+
+        let mut face_normals = Vec::with_capacity(triangles.len());
+        let mut edge_normals = HashMap::new();
+        let mut vertex_normals = vec![Vector3::zeros(); positions.len()];
+        for tri in triangles.iter() {
+            let e1 = positions[tri[1]] - positions[tri[0]];
+            let e2 = positions[tri[2]] - positions[tri[0]];
+            let normal = e1
+                .cross(&e2)
+                .try_normalize(0.0)
+                .unwrap_or_else(Vector3::zeros);
+            face_normals.push(normal);
+
+            for corner in 0..3 {
+                let (i, j) = (tri[corner], tri[(corner + 1) % 3]);
+                *edge_normals
+                    .entry((i.min(j), i.max(j)))
+                    .or_insert_with(Vector3::zeros) += normal * PI;
+
+                let a = positions[j] - positions[i];
+                let b = positions[tri[(corner + 2) % 3]] - positions[i];
+                vertex_normals[i] += normal * a.angle(&b);
+            }
+        }
+
+        let mut rv = MeshDistance {
+            positions,
+            triangles,
+            face_normals,
+            edge_normals,
+            vertex_normals,
+            nodes: vec![],
+        };
+        let all = (0..rv.triangles.len()).collect();
+        rv.build(all);
+        rv
+    }
+
+    /// The closest point on the mesh to `point`.
+    pub fn closest_point(&self, point: &Point3<f32>) -> ClosestPoint {
+        self.closest(point).0
+    }
+
+    /// The unsigned distance from `point` to the mesh.
+    pub fn distance(&self, point: &Point3<f32>) -> f32 {
+        self.closest(point).0.distance
+    }
+
+    /// The distance from `point` to the mesh, negative inside it.
+    pub fn signed_distance(&self, point: &Point3<f32>) -> f32 {
+        let (closest, feature) = self.closest(point);
+        let tri = self.triangles[closest.triangle];
+        let pseudo_normal = match feature {
+            Feature::Vertex(c) => self.vertex_normals[tri[c]],
+            Feature::Edge(c1, c2) => {
+                let (i, j) = (tri[c1], tri[c2]);
+                self.edge_normals[&(i.min(j), i.max(j))]
+            }
+            Feature::Face => self.face_normals[closest.triangle],
+        };
+
+        if (point - closest.point).dot(&pseudo_normal) < 0.0 {
+            -closest.distance
+        } else {
+            closest.distance
+        }
+    }
+
+    /// Samples the signed distance on a grid, for fast (interpolated)
+    /// lookups with `ScalarGrid::value_at`. Running marching cubes on
+    /// the result at a nonzero isovalue gives an offset surface.
+    pub fn bake(&self, min: Point3<f32>, max: Point3<f32>, resolution: [usize; 3]) -> ScalarGrid {
+        ScalarGrid::sample(min, max, resolution, |p| self.signed_distance(&p))
+    }
+
+    fn closest(&self, point: &Point3<f32>) -> (ClosestPoint, Feature) {
+        let p = point.coords;
+        let mut best: Option<(ClosestPoint, Feature)> = None;
+        let mut best_dist2 = f32::INFINITY;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if box_distance2(&node.min, &node.max, &p) >= best_dist2 {
+                continue;
+            }
+
+            match node.contents {
+                NodeContents::Leaf(ref triangles) => {
+                    for &t in triangles.iter() {
+                        let (closest, feature) = self.closest_on_triangle(t, &p);
+                        let dist2 = (closest - p).norm_squared();
+                        if dist2 < best_dist2 {
+                            best_dist2 = dist2;
+                            best = Some((
+                                ClosestPoint {
+                                    point: Point3::from(closest),
+                                    triangle: t,
+                                    distance: dist2.sqrt(),
+                                },
+                                feature,
+                            ));
+                        }
+                    }
+                }
+                // Visit the nearer child first, so that it's more
+                // likely the farther one can be skipped.
+                NodeContents::Branch(left, right) => {
+                    let (l, r) = (&self.nodes[left], &self.nodes[right]);
+                    if box_distance2(&l.min, &l.max, &p) < box_distance2(&r.min, &r.max, &p) {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                }
+            }
+        }
+
+        best.unwrap()
+    }
+
+    // From Ericson, "Real-Time Collision Detection", section 5.1.5.
+    fn closest_on_triangle(&self, t: usize, p: &Vector3<f32>) -> (Vector3<f32>, Feature) {
+        let [ia, ib, ic] = self.triangles[t];
+        let (a, b, c) = (self.positions[ia], self.positions[ib], self.positions[ic]);
+        let (ab, ac, ap) = (b - a, c - a, p - a);
+
+        let d1 = ab.dot(&ap);
+        let d2 = ac.dot(&ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return (a, Feature::Vertex(0));
+        }
+
+        let bp = p - b;
+        let d3 = ab.dot(&bp);
+        let d4 = ac.dot(&bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return (b, Feature::Vertex(1));
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return (a + ab * (d1 / (d1 - d3)), Feature::Edge(0, 1));
+        }
+
+        let cp = p - c;
+        let d5 = ab.dot(&cp);
+        let d6 = ac.dot(&cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return (c, Feature::Vertex(2));
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return (a + ac * (d2 / (d2 - d6)), Feature::Edge(0, 2));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+            return (b + (c - b) * w, Feature::Edge(1, 2));
+        }
+
+        let denom = 1.0 / (va + vb + vc);
+        (a + ab * (vb * denom) + ac * (vc * denom), Feature::Face)
+    }
+
+    // Splits the triangles at the median of their centroids along the
+    // longest axis of their bounds, until there are few enough to put
+    // in a leaf. Returns the index of the new node.
+    fn build(&mut self, mut triangles: Vec<usize>) -> usize {
+        let mut min = Vector3::repeat(f32::INFINITY);
+        let mut max = Vector3::repeat(f32::NEG_INFINITY);
+        for &t in triangles.iter() {
+            for &i in self.triangles[t].iter() {
+                min = min.inf(&self.positions[i]);
+                max = max.sup(&self.positions[i]);
+            }
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            contents: NodeContents::Branch(0, 0),
+        });
+
+        if triangles.len() <= LEAF_SIZE {
+            self.nodes[index].contents = NodeContents::Leaf(triangles);
+            return index;
+        }
+
+        let axis = (max - min).imax();
+        let centroid = |t: usize| {
+            let [a, b, c] = self.triangles[t];
+            self.positions[a][axis] + self.positions[b][axis] + self.positions[c][axis]
+        };
+        triangles.sort_by(|a, b| centroid(*a).partial_cmp(&centroid(*b)).unwrap());
+        let upper = triangles.split_off(triangles.len() / 2);
+
+        let left = self.build(triangles);
+        let right = self.build(upper);
+        self.nodes[index].contents = NodeContents::Branch(left, right);
+        index
+    }
+}
+
+fn box_distance2(min: &Vector3<f32>, max: &Vector3<f32>, p: &Vector3<f32>) -> f32 {
+    let mut dist2 = 0.0;
+    for axis in 0..3 {
+        let d = (min[axis] - p[axis]).max(p[axis] - max[axis]).max(0.0);
+        dist2 += d * d;
+    }
+    dist2
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::MeshDistance;
+    use crate::geometry::{marching_cubes, octohedron_data, ScalarGrid};
+
+    fn octohedron() -> MeshDistance {
+        let (verts, elems) = octohedron_data();
+        MeshDistance::new(&verts, &elems)
+    }
+
+    #[test]
+    fn octohedron_distances() {
+        let mesh = octohedron();
+
+        // The faces are 1 / sqrt(3) from the center.
+        let inside = mesh.signed_distance(&Point3::origin());
+        assert!((inside + 1.0 / 3.0f32.sqrt()).abs() < 1e-6);
+
+        // Closest to a vertex.
+        assert!((mesh.signed_distance(&Point3::new(2.0, 0.0, 0.0)) - 1.0).abs() < 1e-6);
+
+        // Closest to an edge.
+        let closest = mesh.closest_point(&Point3::new(1.0, 0.0, 1.0));
+        assert!((closest.point - Point3::new(0.5, 0.0, 0.5)).norm() < 1e-6);
+        assert!((mesh.signed_distance(&Point3::new(1.0, 0.0, 1.0)) - 0.5f32.sqrt()).abs() < 1e-6);
+
+        // Just inside, near an edge.
+        assert!(mesh.signed_distance(&Point3::new(0.49, 0.0, 0.49)) < 0.0);
+    }
+
+    #[test]
+    fn sphere_distances() {
+        // A fine enough mesh that it's nearly the unit sphere, with
+        // enough triangles to exercise the hierarchy.
+        let grid = ScalarGrid::sample(
+            Point3::new(-1.5, -1.5, -1.5),
+            Point3::new(1.5, 1.5, 1.5),
+            [24, 24, 24],
+            |p| p.coords.norm() - 1.0,
+        );
+        let (verts, elems) = marching_cubes(&grid, 0.0);
+        let mesh = MeshDistance::new(&verts, &elems);
+
+        for &(x, y, z) in [
+            (0.0, 0.0, 0.0),
+            (0.3, -0.2, 0.1),
+            (0.9, 0.1, 0.0),
+            (1.2, 1.2, -0.4),
+            (-3.0, 0.5, 2.0),
+        ]
+        .iter()
+        {
+            let p = Point3::new(x, y, z);
+            let expected = p.coords.norm() - 1.0;
+            let actual = mesh.signed_distance(&p);
+            assert!(
+                (actual - expected).abs() < 0.03,
+                "{}: {} != {}",
+                p,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn baked_lookup() {
+        let mesh = octohedron();
+        let grid = mesh.bake(
+            Point3::new(-2.0, -2.0, -2.0),
+            Point3::new(2.0, 2.0, 2.0),
+            [5, 5, 5],
+        );
+        assert_eq!(mesh.signed_distance(&Point3::origin()), grid.value(2, 2, 2));
+        assert_eq!(
+            mesh.signed_distance(&Point3::new(2.0, 0.0, 0.0)),
+            grid.value_at(&Point3::new(2.0, 0.0, 0.0))
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use glium::{backend::Facade, index::PrimitiveType};
+use nalgebra::{Point3, Vector3};
+
+use super::{Geometry, PCNVertex};
+
+/// Samples of a scalar field on a regular grid over a bounding box.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScalarGrid {
+    min: Point3<f32>,
+    max: Point3<f32>,
+    resolution: [usize; 3],
+    values: Vec<f32>,
+}
+
+impl ScalarGrid {
+    /// Wraps existing samples (e.g. a physics density field), stored
+    /// with x varying fastest, then y, then z. There must be at least
+    /// two samples along each axis.
+    pub fn new(
+        min: Point3<f32>,
+        max: Point3<f32>,
+        resolution: [usize; 3],
+        values: Vec<f32>,
+    ) -> ScalarGrid {
+        assert!(resolution.iter().all(|r| *r >= 2));
+        assert_eq!(resolution[0] * resolution[1] * resolution[2], values.len());
+        ScalarGrid {
+            min,
+            max,
+            resolution,
+            values,
+        }
+    }
+
+    /// Samples `field` at `resolution` evenly-spaced points along each
+    /// axis of the box from `min` to `max`.
+    pub fn sample<F: Fn(Point3<f32>) -> f32>(
+        min: Point3<f32>,
+        max: Point3<f32>,
+        resolution: [usize; 3],
+        field: F,
+    ) -> ScalarGrid {
+        let mut grid = ScalarGrid::new(
+            min,
+            max,
+            resolution,
+            vec![0.0; resolution[0] * resolution[1] * resolution[2]],
+        );
+        for z in 0..resolution[2] {
+            for y in 0..resolution[1] {
+                for x in 0..resolution[0] {
+                    let i = grid.index(x, y, z);
+                    grid.values[i] = field(grid.point(x, y, z));
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    pub fn value(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[self.index(x, y, z)]
+    }
+
+    /// The position of the sample at the given grid coordinates.
+    pub fn point(&self, x: usize, y: usize, z: usize) -> Point3<f32> {
+        let step = self.step();
+        self.min + Vector3::new(x as f32 * step.x, y as f32 * step.y, z as f32 * step.z)
+    }
+
+    /// The trilinearly-interpolated value of the field at any point.
+    /// Points outside the grid get the value at the nearest point on
+    /// its boundary.
+    pub fn value_at(&self, point: &Point3<f32>) -> f32 {
+        let step = self.step();
+        let mut cell = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let scaled = ((point[axis] - self.min[axis]) / step[axis])
+                .clamp(0.0, (self.resolution[axis] - 1) as f32);
+            cell[axis] = (scaled.floor() as usize).min(self.resolution[axis] - 2);
+            frac[axis] = scaled - cell[axis] as f32;
+        }
+
+        let mut value = 0.0;
+        for c in 0..8 {
+            let offset = [c & 1, (c >> 1) & 1, (c >> 2) & 1];
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                weight *= if offset[axis] == 1 {
+                    frac[axis]
+                } else {
+                    1.0 - frac[axis]
+                };
+            }
+            value += weight
+                * self.value(
+                    cell[0] + offset[0],
+                    cell[1] + offset[1],
+                    cell[2] + offset[2],
+                );
+        }
+        value
+    }
+
+    /// The gradient of the field at a sample, from central differences
+    /// (one-sided at the edges of the grid).
+    pub fn gradient(&self, x: usize, y: usize, z: usize) -> Vector3<f32> {
+        let step = self.step();
+        let coords = [x, y, z];
+        let mut gradient = Vector3::zeros();
+        for axis in 0..3 {
+            let mut lo = coords;
+            let mut hi = coords;
+            lo[axis] = coords[axis].saturating_sub(1);
+            hi[axis] = (coords[axis] + 1).min(self.resolution[axis] - 1);
+            let dist = (hi[axis] - lo[axis]) as f32 * step[axis];
+            gradient[axis] =
+                (self.value(hi[0], hi[1], hi[2]) - self.value(lo[0], lo[1], lo[2])) / dist;
+        }
+        gradient
+    }
+
+    fn step(&self) -> Vector3<f32> {
+        let size = self.max - self.min;
+        Vector3::new(
+            size.x / (self.resolution[0] - 1) as f32,
+            size.y / (self.resolution[1] - 1) as f32,
+            size.z / (self.resolution[2] - 1) as f32,
+        )
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + self.resolution[0] * (y + self.resolution[1] * z)
+    }
+}
+
+/// Extracts the surface where the field equals `isovalue` as a welded
+/// triangle mesh, with normals from the field's gradient. Values below
+/// the isovalue are inside the surface (as with a signed distance
+/// function), so negate density-like fields first.
+pub fn marching_cubes(grid: &ScalarGrid, isovalue: f32) -> (Vec<PCNVertex>, Vec<u32>) {
+    let cases = case_table();
+    let [nx, ny, nz] = grid.resolution;
+
+    let mut verts = vec![];
+    let mut elems = vec![];
+    let mut edge_verts: HashMap<([usize; 3], usize), u32> = HashMap::new();
+
+    for z in 0..(nz - 1) {
+        for y in 0..(ny - 1) {
+            for x in 0..(nx - 1) {
+                let corner = |c: usize| [x + (c & 1), y + ((c >> 1) & 1), z + ((c >> 2) & 1)];
+
+                let mut case = 0;
+                for c in 0..8 {
+                    let [cx, cy, cz] = corner(c);
+                    if grid.value(cx, cy, cz) < isovalue {
+                        case |= 1 << c;
+                    }
+                }
+
+                for &edge in cases[case].iter() {
+                    let (a, b) = CUBE_EDGES[edge];
+
+                    // Edges are shared between neighboring cells, so
+                    // identify them by their lower corner and axis.
+                    let key = (corner(a), EDGE_AXES[edge]);
+                    let index = *edge_verts.entry(key).or_insert_with(|| {
+                        verts.push(edge_vertex(grid, corner(a), corner(b), isovalue));
+                        (verts.len() - 1) as u32
+                    });
+                    elems.push(index);
+                }
+            }
+        }
+    }
+
+    (verts, elems)
+}
+
+pub fn isosurface<F: Facade>(
+    facade: &F,
+    grid: &ScalarGrid,
+    isovalue: f32,
+) -> Geometry<PCNVertex, u32> {
+    let (verts, elems) = marching_cubes(grid, isovalue);
+    Geometry::with_bounds(facade, PrimitiveType::TrianglesList, verts, elems)
+}
+
+fn edge_vertex(grid: &ScalarGrid, a: [usize; 3], b: [usize; 3], isovalue: f32) -> PCNVertex {
+    let (va, vb) = (grid.value(a[0], a[1], a[2]), grid.value(b[0], b[1], b[2]));
+    let t = if vb != va {
+        ((isovalue - va) / (vb - va)).clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+
+    let pa = grid.point(a[0], a[1], a[2]).coords;
+    let pb = grid.point(b[0], b[1], b[2]).coords;
+    let ga = grid.gradient(a[0], a[1], a[2]);
+    let gb = grid.gradient(b[0], b[1], b[2]);
+    let gradient = ga + (gb - ga) * t;
+    let normal = if gradient.norm() > 0.0 {
+        gradient.normalize()
+    } else {
+        Vector3::zeros()
+    };
+
+    PCNVertex {
+        position: *(pa + (pb - pa) * t).as_ref(),
+        color: [1.0, 1.0, 1.0, 1.0],
+        normal: *normal.as_ref(),
+    }
+}
+
+// Cube corner c is at (c & 1, (c >> 1) & 1, (c >> 2) & 1).
+static CUBE_EDGES: [(usize, usize); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7),
+];
+
+static EDGE_AXES: [usize; 12] = [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2];
+
+// The corners of each face, counterclockwise as seen from outside
+// the cube.
+static CUBE_FACES: [[usize; 4]; 6] = [
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+];
+
+fn edge_between(a: usize, b: usize) -> usize {
+    CUBE_EDGES
+        .iter()
+        .position(|&(c1, c2)| (c1, c2) == (a, b) || (c1, c2) == (b, a))
+        .unwrap()
+}
+
+// Rather than the usual hand-written table, build the triangles (as
+// lists of cube edges) for each of the 256 inside/outside cases from
+// the way the surface crosses each face of the cube.
+//
+// Walking counterclockwise around a face, each run of inside corners
+// starts at an outside-to-inside crossing and ends at an
+// inside-to-outside one; the surface cuts across the face from the
+// end of each run to its start. This separates the inside corners on
+// ambiguous faces, and since neighboring faces walk their shared edge
+// in opposite directions, the segments join up into closed,
+// consistently-oriented polygons around the inside corners.
+fn case_table() -> Vec<Vec<usize>> {
+    (0..256usize)
+        .map(|case| {
+            let inside = |c: usize| case & (1 << c) != 0;
+            let mut next_edge: HashMap<usize, usize> = HashMap::new();
+
+            for face in CUBE_FACES.iter() {
+                // (edge, entering the inside) for each crossing.
+                let crossings: Vec<(usize, bool)> = (0..4)
+                    .filter_map(|k| {
+                        let (a, b) = (face[k], face[(k + 1) % 4]);
+                        if inside(a) != inside(b) {
+                            Some((edge_between(a, b), inside(b)))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+
+                for (k, &(edge, entering)) in crossings.iter().enumerate() {
+                    if !entering {
+                        let n = crossings.len();
+                        let (start, _) = crossings[(k + n - 1) % n];
+                        next_edge.insert(edge, start);
+                    }
+                }
+            }
+
+            let mut triangles = vec![];
+            while let Some(&first) = next_edge.keys().min() {
+                let mut polygon = vec![first];
+                let mut edge = next_edge.remove(&first).unwrap();
+                while edge != first {
+                    polygon.push(edge);
+                    edge = next_edge.remove(&edge).unwrap();
+                }
+
+                for i in 1..(polygon.len() - 1) {
+                    triangles.extend_from_slice(&[polygon[0], polygon[i + 1], polygon[i]]);
+                }
+            }
+            triangles
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Point3;
+
+    use super::{case_table, marching_cubes, ScalarGrid};
+    use crate::geometry::curvature::boundary_vertices;
+
+    fn sphere_grid() -> ScalarGrid {
+        ScalarGrid::sample(
+            Point3::new(-1.5, -1.5, -1.5),
+            Point3::new(1.5, 1.5, 1.5),
+            [16, 16, 16],
+            |p| p.coords.norm() - 1.0,
+        )
+    }
+
+    #[test]
+    fn interpolation() {
+        let grid = ScalarGrid::sample(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 2.0, 2.0),
+            [3, 3, 3],
+            |p| p.x + 2.0 * p.y - p.z,
+        );
+        let value = grid.value_at(&Point3::new(0.5, 1.25, 1.75));
+        assert!((value - 1.25).abs() < 1e-6);
+        assert_eq!(
+            grid.value(2, 0, 0),
+            grid.value_at(&Point3::new(5.0, -1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn case_table_sizes() {
+        let cases = case_table();
+        assert!(cases[0].is_empty());
+        assert!(cases[255].is_empty());
+
+        // One corner in or out is a single triangle.
+        assert_eq!(3, cases[0b0000_0001].len());
+        assert_eq!(3, cases[0b1111_1110].len());
+
+        // A whole face in is a quad.
+        assert_eq!(6, cases[0b0000_1111].len());
+
+        // Two diagonally opposite corners are separate triangles.
+        assert_eq!(6, cases[0b1000_0001].len());
+    }
+
+    #[test]
+    fn sphere() {
+        let (verts, elems) = marching_cubes(&sphere_grid(), 0.0);
+        assert!(!elems.is_empty());
+
+        for v in verts.iter() {
+            let p = v.position_vec();
+            assert!((p.norm() - 1.0).abs() < 0.05, "{:?}", p);
+
+            // Gradient normals point out of the sphere.
+            assert!(v.normal_vec().dot(&p.normalize()) > 0.95);
+        }
+
+        // Triangles face outward.
+        for tri in elems.chunks(3) {
+            let p: Vec<_> = tri
+                .iter()
+                .map(|i| verts[*i as usize].position_vec())
+                .collect();
+            let face_normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+            assert!(face_normal.dot(&p[0]) > 0.0);
+        }
+
+        // The mesh is welded and closed.
+        assert!(boundary_vertices(verts.len(), &elems).iter().all(|b| !b));
+    }
+}
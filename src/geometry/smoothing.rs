@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+use super::{
+    curvature::{area_weighted_normals, boundary_vertices, cotangent_weights, positions},
+    PCNVertex,
+};
+
+/// How much each neighbor of a vertex contributes to the average it's
+/// moved towards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LaplacianWeights {
+    /// Every neighbor counts equally. Cheap, but also slides vertices
+    /// along the surface towards regions with more vertices.
+    Uniform,
+    /// Neighbors are weighted by the cotangents of the angles opposite
+    /// their edges, which mostly moves vertices along the normal.
+    Cotangent,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SmoothingOptions {
+    pub weights: LaplacianWeights,
+    pub iterations: usize,
+
+    /// Keep vertices on the boundary of an open mesh in place, so that
+    /// the mesh doesn't shrink away from its edges.
+    pub pin_boundary: bool,
+
+    /// Recompute the vertex normals from the smoothed faces afterward.
+    pub recompute_normals: bool,
+}
+
+impl Default for SmoothingOptions {
+    fn default() -> SmoothingOptions {
+        SmoothingOptions {
+            weights: LaplacianWeights::Cotangent,
+            iterations: 10,
+            pin_boundary: true,
+            recompute_normals: true,
+        }
+    }
+}
+
+/// Laplacian smoothing: each iteration moves every vertex `lambda` (0
+/// to 1) of the way towards the weighted average of its neighbors.
+/// This shrinks the mesh as it smooths it.
+pub fn laplacian_smooth(
+    verts: &mut [PCNVertex],
+    elems: &[u32],
+    lambda: f32,
+    options: &SmoothingOptions,
+) {
+    smooth(verts, elems, &[lambda], options);
+}
+
+/// Taubin λ/μ smoothing: each iteration is a Laplacian step with a
+/// positive `lambda` followed by one with a negative `mu`, which
+/// undoes the shrinkage. `mu` should be slightly larger in magnitude
+/// than `lambda`, e.g. λ = 0.5 and μ = -0.53.
+pub fn taubin_smooth(
+    verts: &mut [PCNVertex],
+    elems: &[u32],
+    lambda: f32,
+    mu: f32,
+    options: &SmoothingOptions,
+) {
+    smooth(verts, elems, &[lambda, mu], options);
+}
+
+/// Sets each vertex normal to the area-weighted average of the normals
+/// of the faces around it.
+pub fn recompute_normals(verts: &mut [PCNVertex], elems: &[u32]) {
+    let normals = area_weighted_normals(&positions(verts), elems);
+    for (v, n) in verts.iter_mut().zip(normals.iter()) {
+        if n.norm() > 0.0 {
+            v.normal = *n.normalize().as_ref();
+        }
+    }
+}
+
+fn smooth(verts: &mut [PCNVertex], elems: &[u32], factors: &[f32], options: &SmoothingOptions) {
+    let mut positions = positions(verts);
+    let pinned = if options.pin_boundary {
+        boundary_vertices(verts.len(), elems)
+    } else {
+        vec![false; verts.len()]
+    };
+
+    for _ in 0..options.iterations {
+        for factor in factors.iter() {
+            let offsets = laplacian(&positions, elems, options.weights);
+            for (i, (p, offset)) in positions.iter_mut().zip(offsets.iter()).enumerate() {
+                if !pinned[i] {
+                    *p += offset * *factor;
+                }
+            }
+        }
+    }
+
+    for (v, p) in verts.iter_mut().zip(positions.iter()) {
+        v.position = *p.as_ref();
+    }
+
+    if options.recompute_normals {
+        recompute_normals(verts, elems);
+    }
+}
+
+// The offset from each vertex to the weighted average of its
+// neighbors.
+fn laplacian(
+    positions: &[Vector3<f32>],
+    elems: &[u32],
+    weights: LaplacianWeights,
+) -> Vec<Vector3<f32>> {
+    let edge_weights = match weights {
+        LaplacianWeights::Uniform => {
+            let mut edges = HashMap::new();
+            for tri in elems.chunks_exact(3) {
+                for corner in 0..3 {
+                    let (i, j) = (tri[corner], tri[(corner + 1) % 3]);
+                    edges.insert((i.min(j), i.max(j)), 1.0);
+                }
+            }
+            edges
+        }
+        // Negative weights (from obtuse angles) can make the average
+        // fall outside the neighborhood, so clamp them.
+        LaplacianWeights::Cotangent => {
+            let mut edges = cotangent_weights(positions, elems);
+            for w in edges.values_mut() {
+                *w = w.max(0.0);
+            }
+            edges
+        }
+    };
+
+    let mut sums = vec![Vector3::zeros(); positions.len()];
+    let mut total_weights = vec![0.0; positions.len()];
+    for (&(i, j), w) in edge_weights.iter() {
+        let (i, j) = (i as usize, j as usize);
+        sums[i] += positions[j] * *w;
+        sums[j] += positions[i] * *w;
+        total_weights[i] += w;
+        total_weights[j] += w;
+    }
+
+    sums.iter()
+        .zip(total_weights.iter().zip(positions.iter()))
+        .map(|(sum, (total, p))| {
+            if *total > 0.0 {
+                sum / *total - p
+            } else {
+                Vector3::zeros()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{laplacian_smooth, taubin_smooth, LaplacianWeights, SmoothingOptions};
+    use crate::geometry::{octohedron_data, PCNVertex};
+
+    // A 5x5 grid in the z = 0 plane, with the center vertex pulled up.
+    fn bumpy_grid() -> (Vec<PCNVertex>, Vec<u32>) {
+        let mut verts = vec![];
+        for y in 0..5 {
+            for x in 0..5 {
+                let z = if x == 2 && y == 2 { 1.0 } else { 0.0 };
+                verts.push(PCNVertex {
+                    position: [x as f32, y as f32, z],
+                    ..Default::default()
+                });
+            }
+        }
+        let mut elems = vec![];
+        for y in 0..4 {
+            for x in 0..4 {
+                let i = y * 5 + x;
+                elems.extend_from_slice(&[i, i + 1, i + 6, i, i + 6, i + 5]);
+            }
+        }
+        (verts, elems)
+    }
+
+    #[test]
+    fn smoothing_flattens_bumps() {
+        for weights in [LaplacianWeights::Uniform, LaplacianWeights::Cotangent].iter() {
+            let (mut verts, elems) = bumpy_grid();
+            let options = SmoothingOptions {
+                weights: *weights,
+                ..Default::default()
+            };
+            laplacian_smooth(&mut verts, &elems, 0.5, &options);
+            assert!(verts[12].position[2] < 0.1, "{:?}", verts[12]);
+
+            // The surface now faces up.
+            assert!(verts[12].normal[2] > 0.9);
+        }
+    }
+
+    #[test]
+    fn boundary_pinning() {
+        let (original, elems) = bumpy_grid();
+
+        let mut verts = original.clone();
+        taubin_smooth(&mut verts, &elems, 0.5, -0.53, &Default::default());
+        for i in [0, 1, 2, 3, 4, 5, 9, 10, 14, 15, 19, 20, 21, 22, 23, 24].iter() {
+            assert_eq!(original[*i].position, verts[*i].position);
+        }
+
+        let mut verts = original.clone();
+        let options = SmoothingOptions {
+            pin_boundary: false,
+            ..Default::default()
+        };
+        taubin_smooth(&mut verts, &elems, 0.5, -0.53, &options);
+        assert_ne!(original[0].position, verts[0].position);
+    }
+
+    #[test]
+    fn taubin_shrinks_less() {
+        let radius = |verts: &[PCNVertex]| verts[0].position_vec().norm();
+        let options = SmoothingOptions {
+            iterations: 3,
+            ..Default::default()
+        };
+
+        let (mut laplacian, elems) = octohedron_data();
+        laplacian_smooth(&mut laplacian, &elems, 0.5, &options);
+
+        let (mut taubin, elems) = octohedron_data();
+        taubin_smooth(&mut taubin, &elems, 0.5, -0.53, &options);
+
+        assert!(radius(&taubin) > 2.0 * radius(&laplacian));
+    }
+}
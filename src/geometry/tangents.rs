@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use nalgebra::{Vector2, Vector3};
+
+use super::{PCNTTVertex, PCNTVertex};
+
+/// Adds tangents to a textured mesh for use with normal mapping,
+/// following MikkTSpace's approach:
+///
+/// - Each triangle's tangent is projected into the plane of each
+///   corner's vertex normal before it's accumulated, weighted by the
+///   corner's angle.
+/// - The w component is the handedness of the tangent frame, so that
+///   the bitangent is `w * cross(normal, tangent.xyz)`.
+/// - Triangles that share a vertex but disagree on the handedness
+///   (e.g. at a UV mirror seam) don't share the tangent; the vertex is
+///   split, so opposite tangents don't cancel out.
+///
+/// Returns the new vertices and elements; split vertices are added at
+/// the end.
+pub fn with_tangents(verts: &[PCNTVertex], elems: &[u32]) -> (Vec<PCNTTVertex>, Vec<u32>) {
+    // The sums of the projected tangents, for each vertex and
+    // handedness, and the corners that use them.
+    let mut groups: HashMap<(u32, bool), Vector3<f32>> = HashMap::new();
+    let mut corners: Vec<(u32, bool)> = Vec::with_capacity(elems.len());
+
+    for tri in elems.chunks(3) {
+        if tri.len() < 3 {
+            break;
+        }
+        let p: Vec<Vector3<f32>> = tri
+            .iter()
+            .map(|&i| verts[i as usize].position_vec())
+            .collect();
+        let uv: Vec<Vector2<f32>> = tri
+            .iter()
+            .map(|&i| Vector2::from_row_slice(&verts[i as usize].tex_coords))
+            .collect();
+
+        let e1 = p[1] - p[0];
+        let e2 = p[2] - p[0];
+        let duv1 = uv[1] - uv[0];
+        let duv2 = uv[2] - uv[0];
+
+        // With a degenerate texture mapping, a triangle can't tell us
+        // anything about the tangent frame, but its corners still need
+        // a group.
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        let frame = if det.abs() > f32::EPSILON {
+            let r = 1.0 / det;
+            Some((
+                (e1 * duv2.y - e2 * duv1.y) * r,
+                (e2 * duv1.x - e1 * duv2.x) * r,
+            ))
+        } else {
+            None
+        };
+
+        for corner in 0..3 {
+            let i = tri[corner];
+            let n = verts[i as usize].normal_vec();
+            let (tangent, right_handed) = match frame {
+                Some((t, b)) => (t - n * n.dot(&t), n.cross(&t).dot(&b) >= 0.0),
+                None => (Vector3::zeros(), true),
+            };
+            let angle = (p[(corner + 1) % 3] - p[corner]).angle(&(p[(corner + 2) % 3] - p[corner]));
+            let sum = groups
+                .entry((i, right_handed))
+                .or_insert_with(Vector3::zeros);
+            if tangent.norm() > f32::EPSILON {
+                *sum += tangent.normalize() * angle;
+            }
+            corners.push((i, right_handed));
+        }
+    }
+
+    // Each vertex keeps its index for the first handedness it's used
+    // with; the other one, if any, gets a new vertex.
+    let mut out_verts: Vec<PCNTTVertex> =
+        verts.iter().map(|v| tangent_vertex(v, [0.0; 4])).collect();
+    let mut indices: HashMap<(u32, bool), u32> = HashMap::new();
+    let mut used = vec![false; verts.len()];
+    let mut out_elems = Vec::with_capacity(corners.len());
+    for key in corners {
+        let (i, right_handed) = key;
+        let next_index = out_verts.len() as u32;
+        let index = *indices.entry(key).or_insert_with(|| {
+            if used[i as usize] {
+                out_verts.push(out_verts[i as usize]);
+                next_index
+            } else {
+                used[i as usize] = true;
+                i
+            }
+        });
+        let v = &verts[i as usize];
+        out_verts[index as usize].tangent = tangent(&v.normal_vec(), &groups[&key], right_handed);
+        out_elems.push(index);
+    }
+
+    // Vertices no triangle uses still need a valid frame.
+    for (v, out) in verts.iter().zip(out_verts.iter_mut()) {
+        if out.tangent == [0.0; 4] {
+            out.tangent = tangent(&v.normal_vec(), &Vector3::zeros(), true);
+        }
+    }
+
+    (out_verts, out_elems)
+}
+
+fn tangent_vertex(v: &PCNTVertex, tangent: [f32; 4]) -> PCNTTVertex {
+    PCNTTVertex {
+        position: v.position,
+        color: v.color,
+        normal: v.normal,
+        tex_coords: v.tex_coords,
+        tangent,
+    }
+}
+
+// The final tangent from a sum of projected tangents. Falls back to
+// any vector perpendicular to the normal for vertices without usable
+// texture coordinates.
+fn tangent(n: &Vector3<f32>, sum: &Vector3<f32>, right_handed: bool) -> [f32; 4] {
+    let mut t = sum - n * n.dot(sum);
+    if t.norm() <= f32::EPSILON {
+        t = perpendicular(n);
+    }
+    let t = t.normalize();
+    let handedness = if right_handed { 1.0 } else { -1.0 };
+    [t.x, t.y, t.z, handedness]
+}
+
+fn perpendicular(n: &Vector3<f32>) -> Vector3<f32> {
+    if n.x.abs() < 0.9 {
+        Vector3::x().cross(n)
+    } else {
+        Vector3::y().cross(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_tangents;
+    use crate::geometry::PCNTVertex;
+
+    fn quad(flip_u: bool) -> Vec<PCNTVertex> {
+        let corners = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        corners
+            .iter()
+            .map(|c| PCNTVertex {
+                position: [c[0], c[1], 0.0],
+                color: [1.0, 1.0, 1.0, 1.0],
+                normal: [0.0, 0.0, 1.0],
+                tex_coords: [if flip_u { 1.0 - c[0] } else { c[0] }, c[1]],
+            })
+            .collect()
+    }
+
+    static QUAD_ELEMENTS: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    #[test]
+    fn tangent_follows_u() {
+        let (verts, elems) = with_tangents(&quad(false), &QUAD_ELEMENTS);
+        assert_eq!(QUAD_ELEMENTS.to_vec(), elems);
+        for v in verts {
+            assert_eq!([1.0, 0.0, 0.0, 1.0], v.tangent);
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_handedness() {
+        // Mirroring u flips the tangent, but the bitangent (along v)
+        // stays put, so the frame becomes left-handed.
+        let (verts, _) = with_tangents(&quad(true), &QUAD_ELEMENTS);
+        for v in verts {
+            assert_eq!([-1.0, 0.0, 0.0, -1.0], v.tangent);
+        }
+    }
+
+    #[test]
+    fn split_at_mirror_seam() {
+        // Two quads sharing the edge x = 1, with u mirrored across it,
+        // as when one texture is used for both halves of a model.
+        let verts: Vec<PCNTVertex> = [
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [2.0, 0.0],
+            [0.0, 1.0],
+            [1.0, 1.0],
+            [2.0, 1.0],
+        ]
+        .iter()
+        .map(|c: &[f32; 2]| PCNTVertex {
+            position: [c[0], c[1], 0.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+            normal: [0.0, 0.0, 1.0],
+            tex_coords: [1.0 - (c[0] - 1.0).abs(), c[1]],
+        })
+        .collect();
+        let elems = [0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4];
+        let (out_verts, out_elems) = with_tangents(&verts, &elems);
+
+        // The seam vertices are split, rather than left with the
+        // cancelled-out sum of opposite tangents.
+        assert_eq!(8, out_verts.len());
+        for (corner, &i) in out_elems.iter().enumerate() {
+            let v = &out_verts[i as usize];
+            assert_eq!(verts[elems[corner] as usize].position, v.position);
+            if corner < 6 {
+                assert_eq!([1.0, 0.0, 0.0, 1.0], v.tangent);
+            } else {
+                assert_eq!([-1.0, 0.0, 0.0, -1.0], v.tangent);
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::io;
+
+use glium::{backend::Facade, index::PrimitiveType};
+use nalgebra::Vector3;
+
+use super::{apply_colormap, Colormap, Geometry, PCNVertex, ScalarRange};
+
+/// Heights sampled on a regular grid over a rectangle in the xy
+/// plane, for building z = f(x, y) surfaces and terrain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightField {
+    min: [f32; 2],
+    max: [f32; 2],
+    resolution: [usize; 2],
+    heights: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainOptions {
+    /// Colors the vertices by height. Without one, they're white.
+    pub colormap: Option<Colormap>,
+    pub color_range: ScalarRange,
+
+    /// Hangs a vertical skirt this far down from the edges of the
+    /// surface, so that it looks solid from the side.
+    pub skirt_depth: Option<f32>,
+}
+
+impl Default for TerrainOptions {
+    fn default() -> TerrainOptions {
+        TerrainOptions {
+            colormap: Some(Colormap::Terrain),
+            color_range: ScalarRange::Auto,
+            skirt_depth: None,
+        }
+    }
+}
+
+impl HeightField {
+    /// Samples `height` at `resolution` evenly-spaced points along the
+    /// x and y axes of the rectangle from `min` to `max`.
+    pub fn from_fn<H: Fn(f32, f32) -> f32>(
+        min: [f32; 2],
+        max: [f32; 2],
+        resolution: [usize; 2],
+        height: H,
+    ) -> HeightField {
+        assert!(resolution[0] >= 2 && resolution[1] >= 2);
+        let mut field = HeightField {
+            min,
+            max,
+            resolution,
+            heights: Vec::with_capacity(resolution[0] * resolution[1]),
+        };
+        for j in 0..resolution[1] {
+            for i in 0..resolution[0] {
+                let [x, y] = field.point(i, j);
+                field.heights.push(height(x, y));
+            }
+        }
+        field
+    }
+
+    /// Reads a grayscale heightmap image, with one sample per pixel.
+    /// Black is at height 0 and white at `height_scale`. The top row of
+    /// the image is at the maximum y. The image must be at least 2x2.
+    pub fn from_image(
+        filename: &str,
+        min: [f32; 2],
+        max: [f32; 2],
+        height_scale: f32,
+    ) -> io::Result<HeightField> {
+        let image = image::open(filename).map_err(io::Error::other)?.to_luma16();
+        let (width, height) = image.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        if width < 2 || height < 2 {
+            return Err(io::Error::other(format!(
+                "Heightmap {:?} is {}x{}, but must be at least 2x2",
+                filename, width, height
+            )));
+        }
+
+        let mut heights = Vec::with_capacity(width * height);
+        for j in 0..height {
+            for i in 0..width {
+                let pixel = image.get_pixel(i as u32, (height - 1 - j) as u32);
+                heights.push(pixel[0] as f32 / u16::MAX as f32 * height_scale);
+            }
+        }
+
+        Ok(HeightField {
+            min,
+            max,
+            resolution: [width, height],
+            heights,
+        })
+    }
+
+    pub fn resolution(&self) -> [usize; 2] {
+        self.resolution
+    }
+
+    pub fn height(&self, i: usize, j: usize) -> f32 {
+        self.heights[i + j * self.resolution[0]]
+    }
+
+    /// The x and y coordinates of the sample at the given grid
+    /// coordinates.
+    pub fn point(&self, i: usize, j: usize) -> [f32; 2] {
+        let step = self.step();
+        [
+            self.min[0] + i as f32 * step[0],
+            self.min[1] + j as f32 * step[1],
+        ]
+    }
+
+    /// The surface normal at a sample, from central differences (one
+    /// sided at the edges).
+    pub fn normal(&self, i: usize, j: usize) -> Vector3<f32> {
+        let step = self.step();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.resolution[0] - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.resolution[1] - 1));
+        let dx = (self.height(i1, j) - self.height(i0, j)) / ((i1 - i0) as f32 * step[0]);
+        let dy = (self.height(i, j1) - self.height(i, j0)) / ((j1 - j0) as f32 * step[1]);
+        Vector3::new(-dx, -dy, 1.0).normalize()
+    }
+
+    /// Builds a triangle mesh of the surface, facing +z.
+    pub fn to_mesh(&self, options: &TerrainOptions) -> (Vec<PCNVertex>, Vec<u32>) {
+        let [nx, ny] = self.resolution;
+
+        let mut verts = Vec::with_capacity(nx * ny);
+        for j in 0..ny {
+            for i in 0..nx {
+                let [x, y] = self.point(i, j);
+                verts.push(PCNVertex {
+                    position: [x, y, self.height(i, j)],
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    normal: *self.normal(i, j).as_ref(),
+                });
+            }
+        }
+
+        if let Some(colormap) = options.colormap {
+            apply_colormap(&mut verts, &self.heights, colormap, options.color_range);
+        }
+
+        let mut elems = Vec::with_capacity((nx - 1) * (ny - 1) * 6);
+        for j in 0..(ny - 1) {
+            for i in 0..(nx - 1) {
+                let v = (i + j * nx) as u32;
+                let nx = nx as u32;
+                elems.extend_from_slice(&[v, v + 1, v + nx + 1, v, v + nx + 1, v + nx]);
+            }
+        }
+
+        if let Some(depth) = options.skirt_depth {
+            self.add_skirt(&mut verts, &mut elems, depth);
+        }
+
+        (verts, elems)
+    }
+
+    pub fn to_geometry<F: Facade>(
+        &self,
+        facade: &F,
+        options: &TerrainOptions,
+    ) -> Geometry<PCNVertex, u32> {
+        let (verts, elems) = self.to_mesh(options);
+        Geometry::with_bounds(facade, PrimitiveType::TrianglesList, verts, elems)
+    }
+
+    fn add_skirt(&self, verts: &mut Vec<PCNVertex>, elems: &mut Vec<u32>, depth: f32) {
+        let [nx, ny] = self.resolution;
+
+        // The edge vertices, counterclockwise as seen from above.
+        let mut border = vec![];
+        border.extend(0..nx);
+        border.extend((1..ny).map(|j| (nx - 1) + j * nx));
+        border.extend((0..(nx - 1)).rev().map(|i| i + (ny - 1) * nx));
+        border.extend((1..(ny - 1)).rev().map(|j| j * nx));
+
+        // Each wall quad gets its own vertices, so that it can have an
+        // outward, horizontal normal instead of the surface's.
+        for k in 0..border.len() {
+            let (a, b) = (verts[border[k]], verts[border[(k + 1) % border.len()]]);
+            let edge = b.position_vec() - a.position_vec();
+            let normal = Vector3::new(edge.y, -edge.x, 0.0).normalize();
+
+            let first = verts.len() as u32;
+            for top in [a, b].iter() {
+                let mut wall = PCNVertex {
+                    normal: *normal.as_ref(),
+                    ..*top
+                };
+                verts.push(wall);
+                wall.position[2] -= depth;
+                verts.push(wall);
+            }
+            let (a_top, a_bottom, b_top, b_bottom) = (first, first + 1, first + 2, first + 3);
+            elems.extend_from_slice(&[a_top, a_bottom, b_top, b_top, a_bottom, b_bottom]);
+        }
+    }
+
+    fn step(&self) -> [f32; 2] {
+        [
+            (self.max[0] - self.min[0]) / (self.resolution[0] - 1) as f32,
+            (self.max[1] - self.min[1]) / (self.resolution[1] - 1) as f32,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeightField, TerrainOptions};
+    use crate::geometry::Colormap;
+
+    fn slope() -> HeightField {
+        HeightField::from_fn([-1.0, -1.0], [1.0, 1.0], [5, 3], |x, _| x)
+    }
+
+    #[test]
+    fn samples_function() {
+        let field = slope();
+        assert_eq!([-1.0, -1.0], field.point(0, 0));
+        assert_eq!([1.0, 1.0], field.point(4, 2));
+        assert_eq!(0.5, field.height(3, 1));
+    }
+
+    #[test]
+    fn finite_difference_normals() {
+        let field = slope();
+        let expected = nalgebra::Vector3::new(-1.0, 0.0, 1.0).normalize();
+        for (i, j) in [(0, 0), (2, 1), (4, 2)].iter() {
+            assert!((field.normal(*i, *j) - expected).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn mesh_faces_up_and_is_colored_by_height() {
+        let (verts, elems) = slope().to_mesh(&Default::default());
+        assert_eq!(15, verts.len());
+        assert_eq!(4 * 2 * 6, elems.len());
+
+        for tri in elems.chunks(3) {
+            let p: Vec<_> = tri
+                .iter()
+                .map(|i| verts[*i as usize].position_vec())
+                .collect();
+            assert!((p[1] - p[0]).cross(&(p[2] - p[0])).z > 0.0);
+        }
+
+        assert_eq!(Colormap::Terrain.color(0.0), verts[0].color);
+        assert_eq!(Colormap::Terrain.color(1.0), verts[4].color);
+    }
+
+    #[test]
+    fn rejects_bad_heightmaps() {
+        let path =
+            std::env::temp_dir().join(format!("graphplay2-terrain-{}.png", std::process::id()));
+        image::GrayImage::new(1, 4).save(&path).unwrap();
+        let from_image =
+            |filename: &str| HeightField::from_image(filename, [-1.0, -1.0], [1.0, 1.0], 1.0);
+        assert!(from_image(path.to_str().unwrap()).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(from_image("nowhere.png").is_err());
+    }
+
+    #[test]
+    fn skirt_closes_the_sides() {
+        let options = TerrainOptions {
+            skirt_depth: Some(0.5),
+            ..Default::default()
+        };
+        let (verts, elems) = slope().to_mesh(&options);
+
+        // A quad of four vertices for each of the 12 edges.
+        assert_eq!(15 + 12 * 4, verts.len());
+        assert_eq!(4 * 2 * 6 + 12 * 6, elems.len());
+        assert_eq!(-1.0, verts[15].position[2]);
+        assert_eq!(-1.5, verts[16].position[2]);
+
+        // The walls face outwards, and are lit that way.
+        for tri in elems[(4 * 2 * 6)..].chunks(3) {
+            let v: Vec<_> = tri.iter().map(|i| verts[*i as usize]).collect();
+            let p: Vec<_> = v.iter().map(|v| v.position_vec()).collect();
+            let face_normal = (p[1] - p[0]).cross(&(p[2] - p[0])).normalize();
+            let center = (p[0] + p[1] + p[2]) / 3.0;
+            assert!(face_normal.x * center.x + face_normal.y * center.y > 0.0);
+            for v in v.iter() {
+                assert!((v.normal_vec() - face_normal).norm() < 1e-6);
+            }
+        }
+    }
+}
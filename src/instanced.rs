@@ -0,0 +1,276 @@
+use std::rc::Rc;
+
+use glium::{
+    backend::Facade,
+    index::{Index, IndicesSource},
+    vertex::VerticesSource,
+    Program, Vertex, VertexBuffer,
+};
+use nalgebra::{Matrix4, Point3, Rotation3, Similarity3, Translation3};
+use num::One;
+
+use crate::{
+    geometry::{BoundingSphere, Geometry},
+    material::Material,
+    scene::{DrawState, SceneObject},
+    shaders::ModelTransformation,
+};
+
+/// The per-instance attributes of an `InstancedMesh`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instance {
+    pub instance_model: [[f32; 4]; 4],
+    pub instance_model_normal: [[f32; 3]; 3],
+
+    /// Multiplied with the vertex colors.
+    pub instance_color: [f32; 4],
+}
+
+implement_vertex!(
+    Instance,
+    instance_model,
+    instance_model_normal,
+    instance_color
+);
+
+impl Instance {
+    /// An instance with an arbitrary model matrix.
+    pub fn new(model: Matrix4<f32>, color: [f32; 4]) -> Instance {
+        let transform = ModelTransformation::from_model(model);
+        Instance {
+            instance_model: *transform.model.as_ref(),
+            instance_model_normal: *transform.model_normal.as_ref(),
+            instance_color: color,
+        }
+    }
+
+    /// An instance moved, rotated and uniformly scaled.
+    pub fn from_parts(
+        position: Point3<f32>,
+        orientation: Rotation3<f32>,
+        scale: f32,
+        color: [f32; 4],
+    ) -> Instance {
+        let transform = Similarity3::from_parts(
+            Translation3::from(position.coords),
+            orientation.into(),
+            scale,
+        );
+        Instance::new(transform.to_homogeneous(), color)
+    }
+}
+
+/// One geometry drawn many times (e.g. particles or graph nodes) with
+/// a single instanced draw call. Meant to be drawn with the
+/// `shaders::instanced_lit` or `shaders::instanced_unlit` programs.
+pub struct InstancedMesh<V: Vertex, I: Index> {
+    geometry: Rc<Geometry<V, I>>,
+    program: Rc<Program>,
+    instance_buffer: VertexBuffer<Instance>,
+
+    // A sphere around all of the instances, if the geometry has one.
+    bounds: Option<BoundingSphere>,
+
+    pub draw_state: DrawState,
+
+    pub material: Material,
+
+    /// See `SceneObject::casts_shadows`.
+    pub cast_shadows: bool,
+
+    /// See `SceneObject::receives_shadows`.
+    pub receive_shadows: bool,
+
+    /// See `SceneObject::is_transparent`.
+    pub transparent: bool,
+}
+
+impl<V: Vertex, I: Index> InstancedMesh<V, I> {
+    pub fn new<F: Facade>(
+        facade: &F,
+        geometry: Rc<Geometry<V, I>>,
+        program: Rc<Program>,
+        instances: &[Instance],
+    ) -> InstancedMesh<V, I> {
+        InstancedMesh {
+            bounds: instance_bounds(geometry.bounds(), instances),
+            geometry,
+            program,
+            instance_buffer: VertexBuffer::dynamic(facade, instances).unwrap(),
+            draw_state: DrawState::default(),
+            material: Material::default(),
+            cast_shadows: true,
+            receive_shadows: true,
+            transparent: false,
+        }
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.instance_buffer.len()
+    }
+
+    /// Replaces the instances. The existing buffer is rewritten in
+    /// place if the number of instances hasn't changed.
+    pub fn set_instances<F: Facade>(&mut self, facade: &F, instances: &[Instance]) {
+        self.bounds = instance_bounds(self.geometry.bounds(), instances);
+        if instances.len() == self.instance_buffer.len() {
+            self.instance_buffer.write(instances);
+        } else {
+            self.instance_buffer = VertexBuffer::dynamic(facade, instances).unwrap();
+        }
+    }
+
+    /// Updates a single instance. The bounds only grow to cover its
+    /// new place, so moving single instances around a lot can make
+    /// culling less effective until the next `set_instances`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` isn't less than `instance_count()`.
+    pub fn set_instance(&mut self, index: usize, instance: Instance) {
+        assert!(
+            index < self.instance_count(),
+            "Instance {} out of range for {} instances",
+            index,
+            self.instance_count()
+        );
+        if let (Some(bounds), Some(new)) = (
+            self.bounds,
+            instance_bounds(self.geometry.bounds(), &[instance]),
+        ) {
+            self.bounds = Some(bounds.merged(&new));
+        }
+        self.instance_buffer
+            .slice_mut(index..(index + 1))
+            .unwrap()
+            .write(&[instance]);
+    }
+}
+
+impl<V: Vertex, I: Index> SceneObject for InstancedMesh<V, I> {
+    fn vertices(&self) -> VerticesSource<'_> {
+        self.geometry.vertex_buffer().into()
+    }
+
+    fn indices(&self) -> IndicesSource<'_> {
+        IndicesSource::from(self.geometry.index_buffer())
+    }
+
+    fn program(&self) -> &Program {
+        &self.program
+    }
+
+    fn model_transform(&self) -> ModelTransformation {
+        ModelTransformation {
+            model: One::one(),
+            model_normal: One::one(),
+        }
+    }
+
+    fn instances(&self) -> Option<VerticesSource<'_>> {
+        Some(self.instance_buffer.per_instance().unwrap().into())
+    }
+
+    fn draw_state(&self) -> DrawState {
+        self.draw_state
+    }
+
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.cast_shadows
+    }
+
+    fn receives_shadows(&self) -> bool {
+        self.receive_shadows
+    }
+
+    fn replace_program(&mut self, old: &Rc<Program>, new: &Rc<Program>) {
+        if Rc::ptr_eq(&self.program, old) {
+            self.program = new.clone();
+        }
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    fn bounds(&self) -> Option<BoundingSphere> {
+        self.bounds
+    }
+}
+
+// A sphere around a geometry's bounds placed at each of the instances.
+fn instance_bounds(
+    geometry: Option<BoundingSphere>,
+    instances: &[Instance],
+) -> Option<BoundingSphere> {
+    let geometry = geometry?;
+    instances
+        .iter()
+        .map(|instance| geometry.transformed(&Matrix4::from(instance.instance_model)))
+        .fold(None, |bounds: Option<BoundingSphere>, sphere| {
+            Some(bounds.map_or(sphere, |bounds| bounds.merged(&sphere)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Point3, Rotation3, Vector3};
+
+    use super::{instance_bounds, Instance};
+    use crate::geometry::BoundingSphere;
+
+    #[test]
+    fn similarity_instance() {
+        let instance = Instance::from_parts(
+            Point3::new(1.0, 2.0, 3.0),
+            Rotation3::identity(),
+            2.0,
+            [1.0; 4],
+        );
+        assert_eq!([1.0, 2.0, 3.0, 1.0], instance.instance_model[3]);
+        assert_eq!([2.0, 0.0, 0.0, 0.0], instance.instance_model[0]);
+        assert_eq!([0.5, 0.0, 0.0], instance.instance_model_normal[0]);
+    }
+
+    #[test]
+    fn non_uniform_scale_normals() {
+        // Squashing a surface along x tilts its normals towards x.
+        let model = Matrix4::new_nonuniform_scaling(&Vector3::new(0.5, 1.0, 1.0));
+        let instance = Instance::new(model, [1.0; 4]);
+        let normal = nalgebra::Matrix3::from(instance.instance_model_normal)
+            * Vector3::new(1.0, 1.0, 0.0).normalize();
+        assert!(normal.x > normal.y);
+    }
+
+    #[test]
+    fn bounds_cover_every_instance() {
+        let sphere = BoundingSphere {
+            center: Point3::origin(),
+            radius: 1.0,
+        };
+        let instances = [
+            Instance::from_parts(
+                Point3::new(-4.0, 0.0, 0.0),
+                Rotation3::identity(),
+                1.0,
+                [1.0; 4],
+            ),
+            Instance::from_parts(
+                Point3::new(4.0, 0.0, 0.0),
+                Rotation3::identity(),
+                2.0,
+                [1.0; 4],
+            ),
+        ];
+        let bounds = instance_bounds(Some(sphere), &instances).unwrap();
+        assert_eq!(Point3::new(0.5, 0.0, 0.0), bounds.center);
+        assert_eq!(5.5, bounds.radius);
+
+        assert_eq!(None, instance_bounds(None, &instances));
+        assert_eq!(None, instance_bounds(Some(sphere), &[]));
+    }
+}
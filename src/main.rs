@@ -1,20 +1,13 @@
 use std::{
-    cell::RefCell,
-    rc::Rc,
-    thread,
+    env, thread,
     time::{Duration, Instant},
 };
 
-use crate::camera::Camera;
 use glium::{
     glutin::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder, ContextBuilder},
     Display, Surface,
 };
-use crate::mesh::Mesh;
-use nalgebra::{Point3, Vector3};
-use crate::physics::{Body, Spring, System, FRAME_PERIOD};
-use crate::scene::Scene;
-use crate::shaders::LightProperties;
+use crate::physics::FRAME_PERIOD;
 
 extern crate byteorder;
 extern crate nalgebra;
@@ -26,11 +19,17 @@ extern crate glium;
 pub mod camera;
 // pub mod events;
 pub mod geometry;
+pub mod instanced;
+pub mod material;
 pub mod mesh;
+pub mod obj;
 pub mod physics;
 pub mod ply;
+pub mod point_cloud;
 pub mod scene;
+pub mod scene_file;
 pub mod shaders;
+pub mod texture;
 
 fn main() {
     let (width, height) = (1024, 768);
@@ -48,45 +47,26 @@ fn main() {
 
     let (window_width, window_height) = display.gl_window().window().inner_size().into();
 
-    // Create the shader programs.
-    let unlit = Rc::new(shaders::unlit(&display));
-    let lit = Rc::new(shaders::lit(&display));
-
-    // Create the scene graph.
-    let mut scene = Scene::new(
+    // Load the scene and its physics.
+    let mut loaded = scene_file::load_scene(
         &display,
-        Camera::new(
-            Point3::new(0.0, 0.0, 70.0),
-            Point3::new(0.0, 0.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-        ),
+        "scenes/demo.scene",
         window_width,
         window_height,
-    );
-    scene.set_light(
-        0,
-        LightProperties::new(true, [0.0, 10.0, 10.0], [1.0, 1.0, 1.0, 1.0], 10.0),
-    );
-
-    // Bunny.
-    let bunny = Rc::new(geometry::load_ply(&display, "geometry/stanford_bunny.ply"));
-    let bunny_mesh = Rc::new(RefCell::new(Mesh::new(bunny, lit)));
-    scene.add_object(bunny_mesh.clone());
-
-    // Cube boundary.
-    let cube = Rc::new(geometry::wireframe_cube(&display));
-    let cube_mesh = Rc::new(RefCell::new(Mesh::new(cube, unlit)));
-    cube_mesh.borrow_mut().scale = 10.0;
-    scene.add_object(cube_mesh.clone());
-
-    // Create the physics environment.
-    let mut world = System::new();
-
-    let mut bunny = Body::new();
-    let origin = Body::new();
-    bunny.set_position(Vector3::new(10.0, 0.0, 0.0));
-    bunny.add_constraint(Rc::new(Spring(5.0)), origin);
-    world.add_body(bunny.clone());
+    )
+    .expect("Error loading scene");
+
+    // With --reload-shaders, rebuild the programs when their files
+    // change.
+    let mut shader_reloader = if env::args().any(|arg| arg == "--reload-shaders") {
+        let mut reloader = shaders::ShaderReloader::new("src/shaders");
+        for (source, program) in loaded.programs.iter() {
+            reloader.watch(source, program.clone());
+        }
+        Some(reloader)
+    } else {
+        None
+    };
 
     // Misc. loop variables.
     let mut prev_time = Instant::now();
@@ -109,20 +89,22 @@ fn main() {
         let ftime = secs + subsecs;
 
         // Update the world.
-        let step_fraction = world.update(ftime);
-        bunny_mesh.borrow_mut().position = bunny.position(step_fraction).into();
+        loaded.update(ftime);
+        if let Some(ref mut reloader) = shader_reloader {
+            reloader.update(&display, &mut loaded.programs, &mut loaded.scene);
+        }
 
         // Update the camera.
         // if events.left_click {
-        //     let theta = -2.0 * PI * events.mouse_delta.x / (scene.viewport().width as f32);
-        //     let phi   = -1.0 * PI * events.mouse_delta.y / (scene.viewport().height as f32);
-        //     scene.camera.rotate(theta, phi);
+        //     let theta = -2.0 * PI * events.mouse_delta.x / (loaded.scene.viewport().width as f32);
+        //     let phi   = -1.0 * PI * events.mouse_delta.y / (loaded.scene.viewport().height as f32);
+        //     loaded.scene.camera.rotate(theta, phi);
         // }
 
         // Render.
         let mut target = display.draw();
         target.clear(None, Some((0.0, 0.0, 0.0, 1.0)), true, Some(1.0), None);
-        scene.render(&mut target);
+        loaded.scene.render(&mut target);
         target.finish().unwrap();
 
         // if events.quit {
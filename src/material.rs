@@ -0,0 +1,65 @@
+use crate::shaders::{MaterialBlock, MaterialProperties};
+
+/// How a surface responds to light. The lit programs multiply the
+/// ambient, diffuse and specular colors with each light's color, and
+/// add the emissive color once regardless of the lights.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+
+    /// The specular exponent; larger values give smaller, sharper
+    /// highlights.
+    pub shininess: f32,
+
+    pub emissive: [f32; 3],
+
+    /// Whether the ambient and diffuse colors are multiplied with the
+    /// vertex colors (and the texture, for textured programs). The
+    /// vertex alpha is always used.
+    pub use_vertex_color: bool,
+}
+
+impl Material {
+    /// A matte material of a single color, ignoring the vertex colors.
+    pub fn from_color(color: [f32; 3]) -> Material {
+        Material {
+            ambient: scaled(color, 0.1),
+            diffuse: scaled(color, 0.7),
+            specular: [0.2; 3],
+            use_vertex_color: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn block(&self) -> MaterialBlock {
+        MaterialBlock {
+            material: MaterialProperties::new(
+                self.ambient,
+                self.diffuse,
+                self.specular,
+                self.shininess,
+                self.emissive,
+                self.use_vertex_color,
+            ),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            ambient: [0.1; 3],
+            diffuse: [0.7; 3],
+            specular: [0.7; 3],
+            shininess: 10.0,
+            emissive: [0.0; 3],
+            use_vertex_color: true,
+        }
+    }
+}
+
+fn scaled(color: [f32; 3], factor: f32) -> [f32; 3] {
+    [color[0] * factor, color[1] * factor, color[2] * factor]
+}
@@ -1,42 +1,81 @@
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
 
 use glium::{
     index::{Index, IndicesSource},
+    texture::{SrgbTexture2d, Texture2d},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction},
     vertex::VerticesSource,
     Program, Vertex,
 };
-use nalgebra::{Point3, Rotation3};
+use nalgebra::{Matrix4, Point3, Rotation3, Vector3};
 use num::One;
 
-use crate::{geometry::Geometry, scene::SceneObject, shaders::ModelTransformation};
+use crate::{
+    geometry::{BoundingSphere, Geometry},
+    material::Material,
+    scene::{DrawState, SceneObject},
+    shaders::{ModelTransformation, ObjectUniforms},
+};
 
 pub struct Mesh<V: Vertex, I: Index> {
     geometry: Rc<Geometry<V, I>>,
     program: Rc<Program>,
 
     pub position: Point3<f32>,
-    pub scale: f32,
+    pub scale: Vector3<f32>,
     pub orientation: Rotation3<f32>,
+
+    /// Passed to the program as `diffuse_texture`.
+    pub texture: Option<Rc<SrgbTexture2d>>,
+
+    /// Passed to the program as `normal_map`.
+    pub normal_map: Option<Rc<Texture2d>>,
+
+    pub draw_state: DrawState,
+
+    pub material: Material,
+
+    /// See `SceneObject::casts_shadows`.
+    pub cast_shadows: bool,
+
+    /// See `SceneObject::receives_shadows`.
+    pub receive_shadows: bool,
+
+    /// See `SceneObject::is_transparent`.
+    pub transparent: bool,
+
+    // The last transform computed, and the parts it was computed from.
+    transform_cache: Cell<Option<(TransformParts, ModelTransformation)>>,
 }
 
+type TransformParts = (Point3<f32>, Vector3<f32>, Rotation3<f32>);
+
 impl<V: Vertex, I: Index> Mesh<V, I> {
     pub fn new(geometry: Rc<Geometry<V, I>>, program: Rc<Program>) -> Mesh<V, I> {
         Mesh {
             geometry,
             program,
             position: Point3::origin(),
-            scale: 1.0,
+            scale: Vector3::repeat(1.0),
             orientation: Rotation3::one(),
+            texture: None,
+            normal_map: None,
+            draw_state: DrawState::default(),
+            material: Material::default(),
+            cast_shadows: true,
+            receive_shadows: true,
+            transparent: false,
+            transform_cache: Cell::new(None),
         }
     }
 }
 
 impl<V: Vertex, I: Index> SceneObject for Mesh<V, I> {
-    fn vertices(&self) -> VerticesSource {
+    fn vertices(&self) -> VerticesSource<'_> {
         self.geometry.vertex_buffer().into()
     }
 
-    fn indices(&self) -> IndicesSource {
+    fn indices(&self) -> IndicesSource<'_> {
         IndicesSource::from(self.geometry.index_buffer())
     }
 
@@ -45,20 +84,115 @@ impl<V: Vertex, I: Index> SceneObject for Mesh<V, I> {
     }
 
     fn model_transform(&self) -> ModelTransformation {
-        // let transform = Similarity3::from_parts(
-        //     Translation::from(self.position.into()),
-        //     self.orientation,
-        //     self.scale);
-        // let model = transform.to_homogeneous();
-
-        // // Since we're only doing uniform scaling, the normal matrix
-        // // (the inverse transpose of the upper-left 3x3 of the model
-        // // matrix) is equal to the upper-left 3x3 of the model matrix.
-        // let model_normal = FromHomogeneous::from(&model);
-
-        ModelTransformation {
-            model: One::one(),
-            model_normal: One::one(),
+        let parts = (self.position, self.scale, self.orientation);
+        match self.transform_cache.get() {
+            Some((cached_parts, transform)) if cached_parts == parts => transform,
+            _ => {
+                let transform = ModelTransformation::from_model(model_matrix(parts));
+                self.transform_cache.set(Some((parts, transform)));
+                transform
+            }
         }
     }
+
+    fn uniforms(&self) -> ObjectUniforms<'_> {
+        let mut uniforms = ObjectUniforms::new();
+        if let Some(ref texture) = self.texture {
+            uniforms = uniforms.add(
+                "diffuse_texture",
+                texture
+                    .sampled()
+                    .wrap_function(SamplerWrapFunction::Repeat)
+                    .minify_filter(MinifySamplerFilter::LinearMipmapLinear)
+                    .magnify_filter(MagnifySamplerFilter::Linear),
+            );
+        }
+        if let Some(ref normal_map) = self.normal_map {
+            uniforms = uniforms.add(
+                "normal_map",
+                normal_map
+                    .sampled()
+                    .wrap_function(SamplerWrapFunction::Repeat)
+                    .minify_filter(MinifySamplerFilter::LinearMipmapLinear)
+                    .magnify_filter(MagnifySamplerFilter::Linear),
+            );
+        }
+        uniforms
+    }
+
+    fn draw_state(&self) -> DrawState {
+        self.draw_state
+    }
+
+    fn material(&self) -> Material {
+        self.material
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.cast_shadows
+    }
+
+    fn receives_shadows(&self) -> bool {
+        self.receive_shadows
+    }
+
+    fn replace_program(&mut self, old: &Rc<Program>, new: &Rc<Program>) {
+        if Rc::ptr_eq(&self.program, old) {
+            self.program = new.clone();
+        }
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.transparent
+    }
+
+    fn bounds(&self) -> Option<BoundingSphere> {
+        self.geometry.bounds()
+    }
+}
+
+// Scale, then rotate, then translate.
+fn model_matrix((position, scale, orientation): TransformParts) -> Matrix4<f32> {
+    Matrix4::new_translation(&position.coords)
+        * orientation.to_homogeneous()
+        * Matrix4::new_nonuniform_scaling(&scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use nalgebra::{Point3, Rotation3, Vector3, Vector4};
+
+    use super::model_matrix;
+    use crate::shaders::ModelTransformation;
+
+    #[test]
+    fn scale_rotate_translate() {
+        let model = model_matrix((
+            Point3::new(10.0, 0.0, 0.0),
+            Vector3::new(2.0, 1.0, 1.0),
+            Rotation3::from_axis_angle(&Vector3::z_axis(), PI / 2.0),
+        ));
+        let p = model * Vector4::new(1.0, 0.0, 0.0, 1.0);
+        assert!((p - Vector4::new(10.0, 2.0, 0.0, 1.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn non_uniform_scale_normals() {
+        // A 45 degree slope squashed to half height gets shallower, so
+        // its normal tilts towards +y.
+        let transform = ModelTransformation::from_model(model_matrix((
+            Point3::origin(),
+            Vector3::new(1.0, 0.5, 1.0),
+            Rotation3::identity(),
+        )));
+        let tangent = transform
+            .model
+            .fixed_slice::<nalgebra::U3, nalgebra::U3>(0, 0)
+            * Vector3::new(1.0, 1.0, 0.0);
+        let normal = transform.model_normal * Vector3::new(-1.0, 1.0, 0.0);
+        assert!(tangent.dot(&normal).abs() < 1e-6);
+        assert!(normal.y > -normal.x);
+    }
 }
@@ -0,0 +1,251 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    str::FromStr,
+};
+
+/// One corner of a face: 0-based indices into the document's
+/// positions, texture coordinates and normals.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FaceVertex {
+    pub position: usize,
+    pub tex_coord: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct Document {
+    positions: Vec<[f32; 3]>,
+    tex_coords: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    faces: Vec<Vec<FaceVertex>>,
+}
+
+impl Document {
+    pub fn from_file(filename: &str) -> io::Result<Document> {
+        let file = File::open(filename)?;
+        Self::from_reader(file)
+    }
+
+    pub fn from_reader<T: Read>(reader: T) -> io::Result<Document> {
+        let file = BufReader::new(reader);
+        let mut rv = Document::default();
+
+        for line_result in file.lines() {
+            let line = line_result?;
+            let line = match line.find('#') {
+                Some(comment_start) => &line[..comment_start],
+                None => &line[..],
+            };
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+
+            match tokens.first() {
+                Some(&"v") => {
+                    let v = parse_floats(&tokens, 3, "vertex position")?;
+                    rv.positions.push([v[0], v[1], v[2]]);
+                }
+                Some(&"vt") => {
+                    // The v coordinate is optional, and defaults to 0.
+                    let vt = parse_floats(&tokens, 1, "texture coordinate")?;
+                    rv.tex_coords
+                        .push([vt[0], vt.get(1).cloned().unwrap_or(0.0)]);
+                }
+                Some(&"vn") => {
+                    let vn = parse_floats(&tokens, 3, "vertex normal")?;
+                    rv.normals.push([vn[0], vn[1], vn[2]]);
+                }
+                Some(&"f") => {
+                    let face = rv.parse_face(&tokens)?;
+                    rv.faces.push(face);
+                }
+                // Groups, materials, smoothing groups, etc.
+                _ => {}
+            }
+        }
+
+        Ok(rv)
+    }
+
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    pub fn tex_coords(&self) -> &[[f32; 2]] {
+        &self.tex_coords
+    }
+
+    pub fn normals(&self) -> &[[f32; 3]] {
+        &self.normals
+    }
+
+    pub fn faces(&self) -> &[Vec<FaceVertex>] {
+        &self.faces
+    }
+
+    fn parse_face(&self, toks: &[&str]) -> io::Result<Vec<FaceVertex>> {
+        if toks.len() < 4 {
+            return Err(other_io_error("Not enough vertices for obj face"));
+        }
+
+        let mut face = Vec::with_capacity(toks.len() - 1);
+        for tok in toks.iter().skip(1) {
+            let mut parts = tok.split('/');
+            let position = parts
+                .next()
+                .ok_or_else(|| other_io_error("Missing obj face vertex index"))?;
+            let tex_coord = parts.next().filter(|s| !s.is_empty());
+            let normal = parts.next().filter(|s| !s.is_empty());
+
+            face.push(FaceVertex {
+                position: resolve_index(position, self.positions.len())?,
+                tex_coord: tex_coord
+                    .map(|t| resolve_index(t, self.tex_coords.len()))
+                    .transpose()?,
+                normal: normal
+                    .map(|n| resolve_index(n, self.normals.len()))
+                    .transpose()?,
+            });
+        }
+
+        Ok(face)
+    }
+}
+
+fn parse_floats(toks: &[&str], min_count: usize, what: &str) -> io::Result<Vec<f32>> {
+    if toks.len() < min_count + 1 {
+        return Err(other_io_error(&format!(
+            "Not enough tokens for obj {}",
+            what
+        )));
+    }
+
+    toks.iter()
+        .skip(1)
+        .map(|tok| {
+            f32::from_str(tok).map_err(|e| {
+                other_io_error(&format!(
+                    "Could not parse obj {} from {:?}: {:?}",
+                    what, tok, e
+                ))
+            })
+        })
+        .collect()
+}
+
+// Obj indices are 1-based, and negative indices count backwards from
+// the most recently defined element.
+fn resolve_index(tok: &str, defined: usize) -> io::Result<usize> {
+    let index = tok.parse::<i64>().map_err(|e| {
+        other_io_error(&format!(
+            "Could not parse obj face index from {:?}: {:?}",
+            tok, e
+        ))
+    })?;
+
+    let resolved = if index < 0 {
+        defined as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= defined as i64 {
+        return Err(other_io_error(&format!(
+            "Obj face index out of range: {}",
+            index
+        )));
+    }
+
+    Ok(resolved as usize)
+}
+
+fn other_io_error(msg: &str) -> io::Error {
+    io::Error::other(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, FaceVertex};
+
+    fn error_description(doc_str: &str) -> String {
+        Document::from_reader(doc_str.as_bytes())
+            .unwrap_err()
+            .to_string()
+    }
+
+    static QUAD: &str = r"# A textured unit quad.
+o quad
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.0 1.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.0
+vt 1.0 1.0
+vt 0.0 1.0
+vn 0.0 0.0 1.0
+usemtl default
+f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+";
+
+    #[test]
+    fn read_attributes() {
+        let doc = Document::from_reader(QUAD.as_bytes()).unwrap();
+        assert_eq!(4, doc.positions().len());
+        assert_eq!([1.0, 1.0, 0.0], doc.positions()[2]);
+        assert_eq!(4, doc.tex_coords().len());
+        assert_eq!([1.0, 0.0], doc.tex_coords()[1]);
+        assert_eq!(vec![[0.0, 0.0, 1.0]], doc.normals());
+    }
+
+    #[test]
+    fn read_faces() {
+        let doc = Document::from_reader(QUAD.as_bytes()).unwrap();
+        assert_eq!(1, doc.faces().len());
+
+        let face = &doc.faces()[0];
+        assert_eq!(4, face.len());
+        assert_eq!(
+            FaceVertex {
+                position: 3,
+                tex_coord: Some(3),
+                normal: Some(0)
+            },
+            face[3]
+        );
+    }
+
+    #[test]
+    fn read_partial_face_vertices() {
+        let doc = Document::from_reader(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3\n".as_bytes(),
+        )
+        .unwrap();
+        let face = &doc.faces()[0];
+        assert_eq!(None, face[0].tex_coord);
+        assert_eq!(Some(0), face[0].normal);
+        assert_eq!(None, face[2].normal);
+    }
+
+    #[test]
+    fn bad_data() {
+        assert_eq!(
+            "Not enough tokens for obj vertex position",
+            error_description("v 1.0 2.0\n")
+        );
+
+        assert_eq!(
+            "Could not parse obj texture coordinate from \"puppy\": ParseFloatError { kind: Invalid }",
+            error_description("vt puppy\n")
+        );
+
+        assert_eq!(
+            "Not enough vertices for obj face",
+            error_description("v 0 0 0\nv 1 0 0\nf 1 2\n")
+        );
+
+        assert_eq!(
+            "Obj face index out of range: 4",
+            error_description("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n")
+        );
+    }
+}
@@ -156,6 +156,12 @@ impl Body {
     }
 }
 
+impl Default for Body {
+    fn default() -> Body {
+        Body::new()
+    }
+}
+
 impl Debug for BodyInner {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Body")
@@ -77,3 +77,9 @@ impl System {
         }
     }
 }
+
+impl Default for System {
+    fn default() -> System {
+        System::new()
+    }
+}
@@ -58,10 +58,7 @@ impl FromStr for DataType {
 
 impl DataType {
     pub fn is_int(&self) -> bool {
-        match *self {
-            DataType::Float32 | DataType::Float64 => false,
-            _ => true,
-        }
+        !matches!(*self, DataType::Float32 | DataType::Float64)
     }
 
     pub fn byte_size(&self) -> i32 {
@@ -92,10 +89,10 @@ impl DataType {
             (Uint32, BinaryLittleEndian) => reader.read_u32::<LittleEndian>().map(|i| i as i64),
             (Uint32, BinaryBigEndian) => reader.read_u32::<BigEndian>().map(|i| i as i64),
 
-            _ => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Cannot decode int for float: self = {:?}", self),
-            )),
+            _ => Err(io::Error::other(format!(
+                "Cannot decode int for float: self = {:?}",
+                self
+            ))),
         }
     }
 
@@ -108,10 +105,10 @@ impl DataType {
             (Float32, BinaryBigEndian) => reader.read_f32::<BigEndian>().map(|i| i as f64),
             (Float64, BinaryLittleEndian) => reader.read_f64::<LittleEndian>(),
             (Float64, BinaryBigEndian) => reader.read_f64::<BigEndian>(),
-            _ => Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Cannot decode float for int: self = {:?}", self),
-            )),
+            _ => Err(io::Error::other(format!(
+                "Cannot decode float for int: self = {:?}",
+                self
+            ))),
         }
     }
 }
@@ -149,9 +146,9 @@ impl Document {
 
         for line_result in (&mut file).lines() {
             let line = line_result?;
-            let tokens: Vec<&str> = (&line).split(" ").collect();
+            let tokens: Vec<&str> = line.split(' ').collect();
 
-            match *tokens.get(0).ok_or(other_io_error(&format!(
+            match *tokens.first().ok_or(other_io_error(&format!(
                 "No tokens on line?! (line = {:?})",
                 line
             )))? {
@@ -246,13 +243,13 @@ impl PropertyValue {
     }
 
     pub fn is_same_variant(&self, other: &PropertyValue) -> bool {
-        match (self, other) {
-            (&PropertyValue::IntScalar(..), &PropertyValue::IntScalar(..)) => true,
-            (&PropertyValue::IntList(..), &PropertyValue::IntList(..)) => true,
-            (&PropertyValue::FloatScalar(..), &PropertyValue::FloatScalar(..)) => true,
-            (&PropertyValue::FloatList(..), &PropertyValue::FloatList(..)) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (&PropertyValue::IntScalar(..), &PropertyValue::IntScalar(..))
+                | (&PropertyValue::IntList(..), &PropertyValue::IntList(..))
+                | (&PropertyValue::FloatScalar(..), &PropertyValue::FloatScalar(..))
+                | (&PropertyValue::FloatList(..), &PropertyValue::FloatList(..))
+        )
     }
 
     pub fn int_scalar(&self) -> Option<&Vec<i64>> {
@@ -286,7 +283,7 @@ impl PropertyValue {
     fn push_ascii_scalar_value(&mut self, value_str: &str) -> Result<(), String> {
         match *self {
             PropertyValue::IntScalar(ref mut ilist) => {
-                let ival = i64::from_str_radix(value_str, 10).map_err(|e| {
+                let ival = value_str.parse::<i64>().map_err(|e| {
                     format!(
                         "Could not parse ply scalar int property value from {:?}: {:?}",
                         value_str, e
@@ -368,8 +365,7 @@ impl Property {
 
 fn parse_format(toks: &Vec<&str>) -> io::Result<Format> {
     Format::from_str(
-        *toks
-            .get(1)
+        toks.get(1)
             .ok_or(other_io_error("Not enough tokens for ply format"))?,
     )
 }
@@ -377,7 +373,7 @@ fn parse_format(toks: &Vec<&str>) -> io::Result<Format> {
 fn parse_comment(toks: &Vec<&str>) -> io::Result<String> {
     Ok(toks.iter().skip(1).fold("".to_string(), |mut acc, &tok| {
         if !acc.is_empty() {
-            acc.push_str(" ");
+            acc.push(' ');
         }
         acc.push_str(tok);
         acc
@@ -391,7 +387,7 @@ fn parse_element(toks: &Vec<&str>) -> io::Result<Element> {
     let count_str = *toks
         .get(2)
         .ok_or(other_io_error("Not enough tokens for ply element count"))?;
-    let count = i32::from_str_radix(count_str, 10).map_err(|e| {
+    let count = count_str.parse::<i32>().map_err(|e| {
         other_io_error(&format!(
             "Could not parse ply element count from {:?}: {:?}",
             count_str, e
@@ -461,7 +457,7 @@ fn read_ascii_element<T: BufRead>(element: &mut Element, file: &mut T) -> io::Re
                 let count_str = toks.next().ok_or(other_io_error(
                     "Not enough tokens for ply list property count",
                 ))?;
-                let count = usize::from_str_radix(count_str, 10).map_err(|e| {
+                let count = count_str.parse::<usize>().map_err(|e| {
                     other_io_error(&format!(
                         "Could not get ply property count from {:?}: {:?}",
                         count_str, e
@@ -560,7 +556,7 @@ fn read_binary_element<T: BufRead>(
 }
 
 fn other_io_error(msg: &str) -> io::Error {
-    io::Error::new(io::ErrorKind::Other, msg)
+    io::Error::other(msg)
 }
 
 #[cfg(test)]
@@ -573,7 +569,7 @@ mod tests {
             .to_string()
     }
 
-    static OCTOHEDRON: &'static str = r"ply
+    static OCTOHEDRON: &str = r"ply
 format ascii 1.0
 comment Simple Test Geometry
 element vertex 6
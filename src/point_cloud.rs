@@ -0,0 +1,100 @@
+use std::rc::Rc;
+
+use glium::{
+    index::{Index, IndicesSource},
+    vertex::VerticesSource,
+    Program, Vertex,
+};
+
+use crate::{
+    geometry::{BoundingSphere, Geometry},
+    material::Material,
+    mesh::Mesh,
+    scene::{DrawState, SceneObject},
+    shaders::{ModelTransformation, ObjectUniforms},
+};
+
+/// A mesh drawn as individual points, e.g. a raw scan loaded from a
+/// ply file without any faces. Meant to be drawn with the
+/// `shaders::points` program.
+pub struct PointCloud<V: Vertex, I: Index> {
+    pub mesh: Mesh<V, I>,
+
+    /// The diameter of each point, in pixels.
+    pub point_size: f32,
+
+    /// If set, points are `point_size` pixels across at this distance
+    /// from the camera, and scale with their distance from it.
+    pub attenuation_distance: Option<f32>,
+
+    /// Draw the points as circles rather than squares.
+    pub round_points: bool,
+}
+
+impl<V: Vertex, I: Index> PointCloud<V, I> {
+    pub fn new(geometry: Rc<Geometry<V, I>>, program: Rc<Program>) -> PointCloud<V, I> {
+        PointCloud {
+            mesh: Mesh::new(geometry, program),
+            point_size: 2.0,
+            attenuation_distance: None,
+            round_points: true,
+        }
+    }
+}
+
+impl<V: Vertex, I: Index> SceneObject for PointCloud<V, I> {
+    fn vertices(&self) -> VerticesSource<'_> {
+        self.mesh.vertices()
+    }
+
+    fn indices(&self) -> IndicesSource<'_> {
+        self.mesh.indices()
+    }
+
+    fn program(&self) -> &Program {
+        self.mesh.program()
+    }
+
+    fn model_transform(&self) -> ModelTransformation {
+        self.mesh.model_transform()
+    }
+
+    fn uniforms(&self) -> ObjectUniforms<'_> {
+        self.mesh
+            .uniforms()
+            .add("point_size", self.point_size)
+            .add(
+                "attenuation_distance",
+                self.attenuation_distance.unwrap_or(0.0),
+            )
+            .add("round_points", self.round_points)
+    }
+
+    fn draw_state(&self) -> DrawState {
+        self.mesh.draw_state()
+    }
+
+    fn material(&self) -> Material {
+        self.mesh.material()
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.mesh.casts_shadows()
+    }
+
+    fn receives_shadows(&self) -> bool {
+        self.mesh.receives_shadows()
+    }
+
+    fn replace_program(&mut self, old: &Rc<Program>, new: &Rc<Program>) {
+        self.mesh.replace_program(old, new)
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.mesh.is_transparent()
+    }
+
+    fn bounds(&self) -> Option<BoundingSphere> {
+        self.mesh.bounds()
+    }
+}
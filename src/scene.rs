@@ -1,34 +1,202 @@
-use std::{cell::RefCell, f32::consts::PI, rc::Rc};
+use std::{
+    cell::RefCell, cmp::Ordering, collections::BTreeMap, error::Error, f32::consts::PI, fmt, rc::Rc,
+};
 
 use glium::{
-    backend::Facade, index::IndicesSource, uniforms::UniformBuffer, vertex::VerticesSource, Depth,
-    DepthTest, DrawParameters, Program, Rect, Surface,
+    backend::{Context, Facade},
+    framebuffer::SimpleFrameBuffer,
+    index::IndicesSource,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption},
+    uniforms::{
+        DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction,
+        UniformBuffer,
+    },
+    vertex::VerticesSource,
+    BackfaceCullingMode, Blend, Depth, DepthTest, DrawParameters, PolygonMode, Program, Rect,
+    Surface,
 };
-use nalgebra::Perspective3;
+use nalgebra::{Matrix4, Orthographic3, Perspective3, Point3, Vector3, Vector4};
+use num::One;
 
 use crate::{
     camera::Camera,
-    shaders::{LightListBlock, LightProperties, ModelTransformation, ViewAndProjectionBlock},
+    geometry::BoundingSphere,
+    material::Material,
+    shaders::{
+        self, LightListBlock, LightProperties, LightType, MaterialBlock, ModelTransformation,
+        ObjectUniforms, ProgramRegistry, ShaderError, ViewAndProjectionBlock, MAX_LIGHTS,
+        MAX_SHADOW_MAPS,
+    },
 };
 
+pub use self::frustum::Frustum;
+pub use self::node::{NodeRef, NodeTable, SceneNode};
+
+pub mod frustum;
+pub mod node;
+
 pub trait SceneObject {
-    fn vertices(&self) -> VerticesSource;
-    fn indices(&self) -> IndicesSource;
+    fn vertices(&self) -> VerticesSource<'_>;
+    fn indices(&self) -> IndicesSource<'_>;
     fn program(&self) -> &Program;
     fn model_transform(&self) -> ModelTransformation;
+
+    /// Per-instance attributes, for objects drawn many times with a
+    /// single instanced draw call.
+    fn instances(&self) -> Option<VerticesSource<'_>> {
+        None
+    }
+
+    fn uniforms(&self) -> ObjectUniforms<'_> {
+        ObjectUniforms::new()
+    }
+
+    fn draw_state(&self) -> DrawState {
+        DrawState::default()
+    }
+
+    /// Passed to the program as the `material_block` uniform block.
+    fn material(&self) -> Material {
+        Material::default()
+    }
+
+    /// A sphere around the object, before its model transform. Objects
+    /// without one are never culled.
+    fn bounds(&self) -> Option<BoundingSphere> {
+        None
+    }
+
+    /// Transparent objects are drawn after all the opaque ones, sorted
+    /// back to front, with alpha blending and without writing depth.
+    fn is_transparent(&self) -> bool {
+        false
+    }
+
+    /// Whether the object is drawn into the shadow maps.
+    fn casts_shadows(&self) -> bool {
+        true
+    }
+
+    /// Whether shadows are drawn on the object.
+    fn receives_shadows(&self) -> bool {
+        true
+    }
+
+    /// Switches from one program to another, e.g. after it's been
+    /// rebuilt, if the object uses the old one.
+    fn replace_program(&mut self, _old: &Rc<Program>, _new: &Rc<Program>) {}
+}
+
+/// Overrides for the scene's default draw parameters (depth testing,
+/// no blending, no culling, filled polygons) for a single object. The
+/// viewport always comes from the scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DrawState {
+    pub blend: Option<Blend>,
+    pub backface_culling: Option<BackfaceCullingMode>,
+    pub polygon_mode: Option<PolygonMode>,
+
+    /// The width of lines, in pixels.
+    pub line_width: Option<f32>,
+
+    /// Whether to write to the depth buffer.
+    pub depth_write: Option<bool>,
+}
+
+impl DrawState {
+    pub fn apply(&self, params: &mut DrawParameters<'_>) {
+        if let Some(blend) = self.blend {
+            params.blend = blend;
+        }
+        if let Some(backface_culling) = self.backface_culling {
+            params.backface_culling = backface_culling;
+        }
+        if let Some(polygon_mode) = self.polygon_mode {
+            params.polygon_mode = polygon_mode;
+        }
+        if let Some(line_width) = self.line_width {
+            params.line_width = Some(line_width);
+        }
+        if let Some(depth_write) = self.depth_write {
+            params.depth.write = depth_write;
+        }
+    }
+}
+
+/// Identifies a node added to a scene. Ids aren't reused, so an id
+/// for a removed node stays invalid.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ObjectId(u64);
+
+/// The width and height of each shadow map, in texels.
+pub const SHADOW_MAP_SIZE: u32 = 1024;
+
+// The uniform names of the shadow maps.
+static SHADOW_MAP_NAMES: [&str; MAX_SHADOW_MAPS] =
+    ["shadow_map0", "shadow_map1", "shadow_map2", "shadow_map3"];
+
+// Objects to draw, with their world transforms.
+type ObjectList = Vec<(Rc<RefCell<dyn SceneObject>>, ModelTransformation)>;
+
+/// Identifies a light added to a scene. Like `ObjectId`s, these
+/// aren't reused.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LightId(u64);
+
+/// The error for adding a light to a scene which already has
+/// `MAX_LIGHTS` of them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TooManyLights;
+
+impl fmt::Display for TooManyLights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "A scene can't have more than {} lights", MAX_LIGHTS)
+    }
+}
+
+impl Error for TooManyLights {}
+
+/// What happened to the objects in the last frame rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub drawn: usize,
+    pub culled: usize,
 }
 
 pub struct Scene {
-    objects: Vec<Rc<RefCell<dyn SceneObject>>>,
+    nodes: NodeTable,
     pub camera: Camera<f32>,
     viewport: Rect,
+    projection: Perspective3<f32>,
+
+    /// Skip drawing objects whose bounds are outside the view.
+    pub frustum_culling: bool,
+    stats: RenderStats,
 
     vp_buffer: UniformBuffer<ViewAndProjectionBlock>,
     light_buffer: UniformBuffer<LightListBlock>,
+
+    // Each light, and its slot in the light buffer.
+    lights: BTreeMap<LightId, (usize, LightProperties)>,
+    next_light_id: u64,
+    material_buffer: UniformBuffer<MaterialBlock>,
+
+    context: Rc<Context>,
+    shadow_maps: Vec<DepthTexture2d>,
+    shadow_program: Rc<Program>,
+    instanced_shadow_program: Rc<Program>,
 }
 
 impl Scene {
-    pub fn new<F: Facade>(display: &F, camera: Camera<f32>, width: u32, height: u32) -> Scene {
+    /// Creates an empty scene, drawing shadow maps with the programs
+    /// from `programs`.
+    pub fn new<F: Facade>(
+        display: &F,
+        camera: Camera<f32>,
+        width: u32,
+        height: u32,
+        programs: &mut ProgramRegistry,
+    ) -> Result<Scene, ShaderError> {
         let view = camera.view_transform();
         let projection = Perspective3::new(width as f32 / height as f32, PI / 6.0, 0.1, 100.0);
 
@@ -38,8 +206,8 @@ impl Scene {
             projection: *projection.as_matrix().as_ref(),
         };
 
-        Scene {
-            objects: Vec::new(),
+        Ok(Scene {
+            nodes: NodeTable::new(),
             camera,
             viewport: Rect {
                 left: 0,
@@ -47,31 +215,153 @@ impl Scene {
                 width,
                 height,
             },
+            projection,
+            frustum_culling: true,
+            stats: RenderStats::default(),
 
             vp_buffer: UniformBuffer::new(display, vp_block).unwrap(),
             light_buffer: UniformBuffer::dynamic(display, Default::default()).unwrap(),
-        }
+            lights: BTreeMap::new(),
+            next_light_id: 0,
+            material_buffer: UniformBuffer::dynamic(display, Material::default().block()).unwrap(),
+
+            context: display.get_context().clone(),
+            shadow_maps: (0..MAX_SHADOW_MAPS)
+                .map(|_| {
+                    DepthTexture2d::empty_with_format(
+                        display,
+                        DepthFormat::I24,
+                        MipmapsOption::NoMipmap,
+                        SHADOW_MAP_SIZE,
+                        SHADOW_MAP_SIZE,
+                    )
+                    .unwrap()
+                })
+                .collect(),
+            shadow_program: programs.get(display, shaders::SHADOW.name)?,
+            instanced_shadow_program: programs.get(display, shaders::INSTANCED_SHADOW.name)?,
+        })
     }
 
     pub fn viewport(&self) -> Rect {
         self.viewport
     }
 
-    pub fn add_object<O: SceneObject + 'static>(&mut self, object: Rc<RefCell<O>>) {
-        self.objects.push(object);
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// The node everything else in the scene is attached to.
+    pub fn root(&self) -> &NodeRef {
+        self.nodes.root()
+    }
+
+    /// Adds an object in a new node under the root.
+    pub fn add_object<O: SceneObject + 'static>(&mut self, object: Rc<RefCell<O>>) -> ObjectId {
+        self.nodes
+            .add(None, &SceneNode::with_object(object))
+            .expect("the root is always there")
+    }
+
+    /// Attaches a node (and its children) under the given parent, or
+    /// the root. Returns `None`, without attaching it, if the parent
+    /// has been removed.
+    pub fn add_node(&mut self, parent: Option<ObjectId>, node: &NodeRef) -> Option<ObjectId> {
+        self.nodes.add(parent, node)
+    }
+
+    pub fn node(&self, id: ObjectId) -> Option<&NodeRef> {
+        self.nodes.get(id)
+    }
+
+    /// Removes a node and everything under it from the scene, and
+    /// returns it.
+    pub fn remove_object(&mut self, id: ObjectId) -> Option<NodeRef> {
+        self.nodes.remove(id)
+    }
+
+    pub fn set_visible(&mut self, id: ObjectId, visible: bool) {
+        if let Some(node) = self.nodes.get(id) {
+            node.borrow_mut().visible = visible;
+        }
+    }
+
+    pub fn set_name(&mut self, id: ObjectId, name: &str) {
+        if let Some(node) = self.nodes.get(id) {
+            node.borrow_mut().name = Some(name.to_string());
+        }
+    }
+
+    /// Switches the scene and every object using one program to
+    /// another.
+    pub fn replace_program(&mut self, old: &Rc<Program>, new: &Rc<Program>) {
+        for program in [&mut self.shadow_program, &mut self.instanced_shadow_program] {
+            if Rc::ptr_eq(program, old) {
+                *program = new.clone();
+            }
+        }
+        for node in SceneNode::descendants(self.nodes.root()) {
+            if let Some(object) = node.borrow().object() {
+                object.borrow_mut().replace_program(old, new);
+            }
+        }
+    }
+
+    /// The first-added node with the given name.
+    pub fn find_object(&self, name: &str) -> Option<ObjectId> {
+        self.nodes
+            .iter()
+            .find(|(_, node)| node.borrow().name.as_deref() == Some(name))
+            .map(|(id, _)| id)
+    }
+
+    /// Adds a light in the first free slot of the light buffer.
+    pub fn add_light(&mut self, light: LightProperties) -> Result<LightId, TooManyLights> {
+        let slot = free_light_slot(&self.lights)?;
+
+        let id = LightId(self.next_light_id);
+        self.next_light_id += 1;
+        self.lights.insert(id, (slot, light));
+        self.light_buffer.map().lights[slot] = light;
+        Ok(id)
+    }
+
+    /// Replaces a light. Unknown ids are ignored.
+    pub fn update_light(&mut self, id: LightId, light: LightProperties) {
+        if let Some((slot, properties)) = self.lights.get_mut(&id) {
+            *properties = light;
+            self.light_buffer.map().lights[*slot] = light;
+        }
+    }
+
+    /// Removes a light from the scene, and returns it.
+    pub fn remove_light(&mut self, id: LightId) -> Option<LightProperties> {
+        let (slot, light) = self.lights.remove(&id)?;
+        self.light_buffer.map().lights[slot] = LightProperties::default();
+        Some(light)
+    }
+
+    pub fn light(&self, id: LightId) -> Option<&LightProperties> {
+        self.lights.get(&id).map(|(_, light)| light)
+    }
+
+    /// The lights in the scene, in the order they were added.
+    pub fn lights(&self) -> impl Iterator<Item = (LightId, &LightProperties)> {
+        self.lights.iter().map(|(id, (_, light))| (*id, light))
     }
 
-    pub fn set_light(&mut self, index: usize, light: LightProperties) {
+    /// See `LightListBlock::ambient`.
+    pub fn set_ambient_light(&mut self, color: [f32; 3]) {
         let mut mapped_lights = self.light_buffer.map();
-        mapped_lights.lights[index] = light;
+        mapped_lights.ambient = color;
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.viewport.width = width;
         self.viewport.height = height;
-        let projection = Perspective3::new(width as f32 / height as f32, PI / 6.0, 0.1, 100.0);
+        self.projection = Perspective3::new(width as f32 / height as f32, PI / 6.0, 0.1, 100.0);
         let mut mapped_vp = self.vp_buffer.map();
-        mapped_vp.projection = *projection.as_matrix().as_ref();
+        mapped_vp.projection = *self.projection.as_matrix().as_ref();
     }
 
     fn update_view(&mut self) {
@@ -82,7 +372,7 @@ impl Scene {
     }
 
     pub fn render<S: Surface>(&mut self, target: &mut S) {
-        let draw_params = DrawParameters {
+        let opaque_params = DrawParameters {
             depth: Depth {
                 write: true,
                 test: DepthTest::IfLess,
@@ -92,28 +382,387 @@ impl Scene {
             ..Default::default()
         };
 
+        // Translucent objects are hidden by opaque ones in front of
+        // them, but don't hide each other.
+        let transparent_params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            depth: Depth {
+                write: false,
+                ..opaque_params.depth
+            },
+            ..opaque_params.clone()
+        };
+
         self.update_view();
+        let view = self.camera.view_transform().to_homogeneous();
+        let frustum = Frustum::from_matrix(&(self.projection.as_matrix() * view));
 
-        for object_cell in self.objects.iter() {
-            let object = object_cell.borrow();
-            let model_transform = object.model_transform();
-
-            let uniforms = uniform! {
-                model: *model_transform.model.as_ref(),
-                model_normal: *model_transform.model_normal.as_ref(),
-                view_and_projection: &self.vp_buffer,
-                light_list: &self.light_buffer,
-            };
-
-            target
-                .draw(
-                    object.vertices(),
-                    object.indices(),
-                    object.program(),
-                    &uniforms,
-                    &draw_params,
-                )
+        let mut opaque = vec![];
+        let mut transparent = vec![];
+        let mut casters = vec![];
+        let mut culled = 0;
+        let frustum_culling = self.frustum_culling;
+        SceneNode::traverse(self.nodes.root(), &One::one(), &mut |node, world| {
+            if let Some(object) = node.object() {
+                let transform = object_transform(world, &*object.borrow());
+
+                // Objects out of view can still cast shadows into it.
+                if object.borrow().casts_shadows() {
+                    casters.push((object.clone(), transform));
+                }
+
+                let bounds = object.borrow().bounds();
+                let visible = match bounds {
+                    Some(bounds) if frustum_culling => {
+                        frustum.intersects(&bounds.transformed(&transform.model))
+                    }
+                    _ => true,
+                };
+
+                if !visible {
+                    culled += 1;
+                } else if object.borrow().is_transparent() {
+                    transparent.push((object.clone(), transform));
+                } else {
+                    opaque.push((object.clone(), transform));
+                }
+            }
+        });
+
+        // Draw the translucent objects back to front, by the depth of
+        // their origins. Overlapping parts of a single object may
+        // still blend in the wrong order.
+        transparent.sort_by(|(_, a), (_, b)| {
+            view_depth(&view, &a.model)
+                .partial_cmp(&view_depth(&view, &b.model))
+                .unwrap_or(Ordering::Equal)
+                .reverse()
+        });
+
+        self.render_shadows(&casters);
+
+        for (object, transform) in opaque.iter() {
+            self.draw_object(target, &*object.borrow(), transform, &opaque_params);
+        }
+        for (object, transform) in transparent.iter() {
+            self.draw_object(target, &*object.borrow(), transform, &transparent_params);
+        }
+
+        self.stats = RenderStats {
+            drawn: opaque.len() + transparent.len(),
+            culled,
+        };
+    }
+
+    // Assigns shadow maps to the lights which cast shadows, and draws
+    // the depth of the casters from each of them.
+    fn render_shadows(&mut self, casters: &ObjectList) {
+        let params = DrawParameters {
+            depth: Depth {
+                write: true,
+                test: DepthTest::IfLess,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let caster_bounds = casters
+            .iter()
+            .filter_map(|(object, transform)| {
+                Some(object.borrow().bounds()?.transformed(&transform.model))
+            })
+            .fold(None, |acc: Option<BoundingSphere>, bounds| match acc {
+                Some(acc) => Some(acc.merged(&bounds)),
+                None => Some(bounds),
+            });
+
+        let mut assigned = vec![];
+        let mut next_map = 0;
+        for (slot, light) in self.lights.values() {
+            if !light.casts_shadows
+                || light.light_type() == LightType::Point
+                || next_map >= MAX_SHADOW_MAPS
+            {
+                assigned.push((*slot, light.with_shadow_map(None)));
+                continue;
+            }
+            let map = next_map;
+            next_map += 1;
+
+            let light_space = light_space(light, caster_bounds.as_ref());
+            assigned.push((*slot, light.with_shadow_map(Some((map, light_space)))));
+
+            let mut framebuffer =
+                SimpleFrameBuffer::depth_only(&self.context, &self.shadow_maps[map]).unwrap();
+            framebuffer.clear_depth(1.0);
+            for (object, transform) in casters.iter() {
+                let object = object.borrow();
+                let mut draw_params = params.clone();
+                object.draw_state().apply(&mut draw_params);
+                draw_params.blend = Blend::default();
+                draw_params.depth.write = true;
+
+                let uniforms = uniform! {
+                    model: *transform.model.as_ref(),
+                    light_space: *light_space.as_ref(),
+                };
+                match object.instances() {
+                    Some(instances) => framebuffer.draw(
+                        (object.vertices(), instances),
+                        object.indices(),
+                        &self.instanced_shadow_program,
+                        &uniforms,
+                        &draw_params,
+                    ),
+                    None => framebuffer.draw(
+                        object.vertices(),
+                        object.indices(),
+                        &self.shadow_program,
+                        &uniforms,
+                        &draw_params,
+                    ),
+                }
                 .unwrap();
+            }
         }
+
+        let mut mapped_lights = self.light_buffer.map();
+        for (slot, light) in assigned {
+            mapped_lights.lights[slot] = light;
+        }
+    }
+
+    fn draw_object<S: Surface>(
+        &self,
+        target: &mut S,
+        object: &dyn SceneObject,
+        transform: &ModelTransformation,
+        default_params: &DrawParameters<'_>,
+    ) {
+        let mut draw_params = default_params.clone();
+        object.draw_state().apply(&mut draw_params);
+        self.material_buffer.write(&object.material().block());
+
+        let uniforms = object
+            .uniforms()
+            .add("model", *transform.model.as_ref())
+            .add("model_normal", *transform.model_normal.as_ref())
+            .add("view_and_projection", &self.vp_buffer)
+            .add("light_list", &self.light_buffer)
+            .add("material_block", &self.material_buffer)
+            .add("receive_shadows", object.receives_shadows());
+        let uniforms = self.shadow_maps.iter().zip(SHADOW_MAP_NAMES.iter()).fold(
+            uniforms,
+            |uniforms, (shadow_map, name)| {
+                uniforms.add(
+                    name,
+                    shadow_map
+                        .sampled()
+                        .depth_texture_comparison(Some(DepthTextureComparison::LessOrEqual))
+                        .wrap_function(SamplerWrapFunction::Clamp)
+                        .minify_filter(MinifySamplerFilter::Linear)
+                        .magnify_filter(MagnifySamplerFilter::Linear),
+                )
+            },
+        );
+
+        match object.instances() {
+            Some(instances) => target.draw(
+                (object.vertices(), instances),
+                object.indices(),
+                object.program(),
+                &uniforms,
+                &draw_params,
+            ),
+            None => target.draw(
+                object.vertices(),
+                object.indices(),
+                object.program(),
+                &uniforms,
+                &draw_params,
+            ),
+        }
+        .unwrap();
+    }
+}
+
+// The object's own model transform, composed with the world transform
+// of its node.
+fn object_transform(world: &Matrix4<f32>, object: &dyn SceneObject) -> ModelTransformation {
+    let world = ModelTransformation::from_model(*world);
+    let local = object.model_transform();
+    ModelTransformation {
+        model: world.model * local.model,
+        model_normal: world.model_normal * local.model_normal,
+    }
+}
+
+// The first slot in the light buffer that none of the lights use.
+fn free_light_slot(
+    lights: &BTreeMap<LightId, (usize, LightProperties)>,
+) -> Result<usize, TooManyLights> {
+    (0..MAX_LIGHTS)
+        .find(|slot| !lights.values().any(|(used, _)| used == slot))
+        .ok_or(TooManyLights)
+}
+
+// The transformation from world space to a light's shadow map. Spot
+// lights look along their axis through their cone, and directional
+// lights look along their direction at the shadow casters' bounds.
+fn light_space(light: &LightProperties, caster_bounds: Option<&BoundingSphere>) -> Matrix4<f32> {
+    let direction = Vector3::from(light.direction).normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+
+    if light.light_type() == LightType::Spot {
+        let eye = Point3::from(light.position);
+        let view = Matrix4::look_at_rh(&eye, &(eye + direction), &up);
+        let fovy = (2.0 * light.spot_outer_cos.acos()).clamp(0.01, PI - 0.01);
+
+        // Fit the depth range to the casters, as far as they're in
+        // front of the light.
+        let (near, far) = match caster_bounds {
+            Some(bounds) => {
+                let distance = (bounds.center - eye).dot(&direction);
+                let far = (distance + bounds.radius).max(0.1);
+                ((distance - bounds.radius).max(far * 0.001), far)
+            }
+            None => (0.1, 100.0),
+        };
+        let projection = Perspective3::new(1.0, fovy, near, far);
+        projection.as_matrix() * view
+    } else {
+        let bounds = caster_bounds.copied().unwrap_or(BoundingSphere {
+            center: Point3::origin(),
+            radius: 50.0,
+        });
+        let radius = bounds.radius.max(0.01);
+        let eye = bounds.center - 2.0 * radius * direction;
+        let view = Matrix4::look_at_rh(&eye, &bounds.center, &up);
+        let projection = Orthographic3::new(-radius, radius, -radius, radius, radius, 3.0 * radius);
+        projection.as_matrix() * view
+    }
+}
+
+// The distance in front of the camera of a model's origin.
+fn view_depth(view: &Matrix4<f32>, model: &Matrix4<f32>) -> f32 {
+    let origin = view * model * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    -origin.z / origin.w
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use glium::{BackfaceCullingMode, Blend, DrawParameters, PolygonMode};
+
+    use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+    use super::{free_light_slot, light_space, view_depth, DrawState, LightId, TooManyLights};
+    use crate::{
+        camera::Camera,
+        geometry::BoundingSphere,
+        shaders::{LightProperties, MAX_LIGHTS},
+    };
+
+    #[test]
+    fn depth_in_front_of_camera() {
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, 10.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let view = camera.view_transform().to_homogeneous();
+        let near = Matrix4::new_translation(&Vector3::new(1.0, 0.0, 5.0));
+        let far = Matrix4::new_translation(&Vector3::new(0.0, 2.0, -5.0));
+        assert!((view_depth(&view, &near) - 5.0).abs() < 1e-5);
+        assert!((view_depth(&view, &far) - 15.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn light_slots_run_out() {
+        let mut lights = BTreeMap::new();
+        for i in 0..MAX_LIGHTS {
+            let slot = free_light_slot(&lights).unwrap();
+            assert_eq!(i, slot);
+            lights.insert(LightId(i as u64), (slot, LightProperties::default()));
+        }
+        assert_eq!(Err(TooManyLights), free_light_slot(&lights));
+
+        // Removing a light frees its slot for the next one.
+        lights.remove(&LightId(3));
+        assert_eq!(Ok(3), free_light_slot(&lights));
+    }
+
+    #[test]
+    fn directional_light_space_covers_casters() {
+        let light = LightProperties::directional([0.0, -1.0, -1.0], [1.0; 4]);
+        let bounds = BoundingSphere {
+            center: Point3::new(5.0, 0.0, 0.0),
+            radius: 2.0,
+        };
+        let transform = light_space(&light, Some(&bounds));
+
+        for offset in [
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, -2.0, 0.0),
+            Vector3::new(0.0, 0.0, 2.0),
+        ]
+        .iter()
+        {
+            for sign in [-1.0, 1.0].iter() {
+                let p = bounds.center + offset * *sign;
+                let clip = transform * Vector4::new(p.x, p.y, p.z, 1.0);
+                let ndc = clip.xyz() / clip.w;
+                assert!(ndc.iter().all(|c| c.abs() <= 1.0 + 1e-5), "{:?}", ndc);
+            }
+        }
+    }
+
+    #[test]
+    fn spot_light_space_covers_distant_casters() {
+        let light = LightProperties::spot([0.0; 3], [0.0, 0.0, -1.0], 0.3, 0.4, [1.0; 4]);
+        let bounds = BoundingSphere {
+            center: Point3::new(0.0, 0.0, -300.0),
+            radius: 20.0,
+        };
+        let transform = light_space(&light, Some(&bounds));
+
+        for z in [-281.0, -300.0, -319.0].iter() {
+            let clip = transform * Vector4::new(0.0, 0.0, *z, 1.0);
+            let depth = clip.z / clip.w;
+            assert!(depth.abs() <= 1.0, "{} at {}", depth, z);
+        }
+    }
+
+    #[test]
+    fn draw_state_overrides() {
+        let mut params = DrawParameters {
+            line_width: Some(3.0),
+            ..Default::default()
+        };
+        params.depth.write = true;
+
+        let state = DrawState {
+            blend: Some(Blend::alpha_blending()),
+            polygon_mode: Some(PolygonMode::Line),
+            depth_write: Some(false),
+            ..Default::default()
+        };
+        state.apply(&mut params);
+
+        assert_eq!(Blend::alpha_blending(), params.blend);
+        assert_eq!(PolygonMode::Line, params.polygon_mode);
+        assert!(!params.depth.write);
+
+        // Unset fields are left alone.
+        assert_eq!(Some(3.0), params.line_width);
+        assert_eq!(
+            BackfaceCullingMode::CullingDisabled,
+            params.backface_culling
+        );
     }
 }
@@ -0,0 +1,75 @@
+use nalgebra::{Matrix4, Vector4};
+
+use crate::geometry::BoundingSphere;
+
+/// The volume a camera can see, as six planes facing into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a combined projection and view matrix
+    /// (Gribb and Hartmann's method). Each plane (a, b, c, d) has
+    /// a unit normal (a, b, c), so that ax + by + cz + d is the signed
+    /// distance of a point from it.
+    pub fn from_matrix(projection_view: &Matrix4<f32>) -> Frustum {
+        let row = |i: usize| projection_view.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let mut planes = [w + x, w - x, w + y, w - y, w + z, w - z];
+        for plane in planes.iter_mut() {
+            let length = plane.xyz().norm();
+            *plane /= length;
+        }
+        Frustum { planes }
+    }
+
+    /// Whether any part of the sphere might be visible.
+    pub fn intersects(&self, sphere: &BoundingSphere) -> bool {
+        let center = sphere.center.to_homogeneous();
+        self.planes
+            .iter()
+            .all(|plane| plane.dot(&center) >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use nalgebra::{Perspective3, Point3, Vector3};
+
+    use super::Frustum;
+    use crate::{camera::Camera, geometry::BoundingSphere};
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: Point3::new(x, y, z),
+            radius,
+        }
+    }
+
+    #[test]
+    fn culls_outside_spheres() {
+        let camera = Camera::new(
+            Point3::new(0.0, 0.0, 10.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let projection = Perspective3::new(1.0, PI / 2.0, 0.1, 100.0);
+        let frustum = Frustum::from_matrix(
+            &(projection.as_matrix() * camera.view_transform().to_homogeneous()),
+        );
+
+        assert!(frustum.intersects(&sphere(0.0, 0.0, 0.0, 1.0)));
+
+        // Behind the camera, and beyond the far plane.
+        assert!(!frustum.intersects(&sphere(0.0, 0.0, 20.0, 1.0)));
+        assert!(!frustum.intersects(&sphere(0.0, 0.0, -100.0, 1.0)));
+
+        // Off to the side (the frustum is 20 wide at the origin), unless
+        // it's big enough to reach in.
+        assert!(!frustum.intersects(&sphere(15.0, 0.0, 0.0, 1.0)));
+        assert!(frustum.intersects(&sphere(15.0, 0.0, 0.0, 5.0)));
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
+};
+
+use nalgebra::Matrix4;
+use num::One;
+
+use super::{ObjectId, SceneObject};
+
+pub type NodeRef = Rc<RefCell<SceneNode>>;
+
+/// A node in the scene graph. Each node has a transform relative to
+/// its parent, and optionally an object, which is drawn with its own
+/// model transform composed with the node's world transform. Nodes
+/// without objects group their children, e.g. the parts of an
+/// articulated model.
+pub struct SceneNode {
+    pub transform: Matrix4<f32>,
+    pub name: Option<String>,
+
+    /// Hidden nodes, and everything under them, aren't drawn.
+    pub visible: bool,
+
+    object: Option<Rc<RefCell<dyn SceneObject>>>,
+    parent: Weak<RefCell<SceneNode>>,
+    children: Vec<NodeRef>,
+}
+
+impl SceneNode {
+    /// An empty group node.
+    pub fn new() -> NodeRef {
+        Rc::new(RefCell::new(SceneNode {
+            transform: One::one(),
+            name: None,
+            visible: true,
+            object: None,
+            parent: Weak::new(),
+            children: vec![],
+        }))
+    }
+
+    pub fn with_object<O: SceneObject + 'static>(object: Rc<RefCell<O>>) -> NodeRef {
+        let node = SceneNode::new();
+        node.borrow_mut().object = Some(object);
+        node
+    }
+
+    pub fn object(&self) -> Option<&Rc<RefCell<dyn SceneObject>>> {
+        self.object.as_ref()
+    }
+
+    pub fn children(&self) -> &[NodeRef] {
+        &self.children
+    }
+
+    pub fn parent(&self) -> Option<NodeRef> {
+        self.parent.upgrade()
+    }
+
+    /// Makes `child` the last child of `parent`, detaching it from its
+    /// current parent first. Panics if `child` is `parent` or one of
+    /// its ancestors.
+    pub fn attach(parent: &NodeRef, child: &NodeRef) {
+        let mut ancestor = Some(parent.clone());
+        while let Some(node) = ancestor {
+            assert!(
+                !Rc::ptr_eq(&node, child),
+                "Can't attach a scene node to itself or its descendants"
+            );
+            ancestor = node.borrow().parent();
+        }
+
+        SceneNode::detach(child);
+        child.borrow_mut().parent = Rc::downgrade(parent);
+        parent.borrow_mut().children.push(child.clone());
+    }
+
+    /// Removes `child` from its parent, if it has one.
+    pub fn detach(child: &NodeRef) {
+        let parent = child.borrow().parent();
+        if let Some(parent) = parent {
+            parent
+                .borrow_mut()
+                .children
+                .retain(|c| !Rc::ptr_eq(c, child));
+            child.borrow_mut().parent = Weak::new();
+        }
+    }
+
+    /// The node's transform composed with those of all its ancestors.
+    pub fn world_transform(node: &NodeRef) -> Matrix4<f32> {
+        let mut transform = node.borrow().transform;
+        let mut ancestor = node.borrow().parent();
+        while let Some(node) = ancestor {
+            transform = node.borrow().transform * transform;
+            ancestor = node.borrow().parent();
+        }
+        transform
+    }
+
+    /// The node and all the nodes under it, visible or not.
+    pub fn descendants(node: &NodeRef) -> Vec<NodeRef> {
+        let mut nodes = vec![node.clone()];
+        for child in node.borrow().children.iter() {
+            nodes.extend(SceneNode::descendants(child));
+        }
+        nodes
+    }
+
+    /// Calls `visit` with each visible node in the tree under `node`
+    /// (itself included), parents before children, along with its
+    /// world transform given that of its parent.
+    pub fn traverse<V: FnMut(&SceneNode, &Matrix4<f32>)>(
+        node: &NodeRef,
+        parent_transform: &Matrix4<f32>,
+        visit: &mut V,
+    ) {
+        let node = node.borrow();
+        if !node.visible {
+            return;
+        }
+        let transform = parent_transform * node.transform;
+        visit(&node, &transform);
+        for child in node.children.iter() {
+            SceneNode::traverse(child, &transform, visit);
+        }
+    }
+}
+
+/// The nodes added to a scene, by id, under its root.
+pub struct NodeTable {
+    root: NodeRef,
+    nodes: BTreeMap<ObjectId, NodeRef>,
+    next_id: u64,
+}
+
+impl NodeTable {
+    pub fn new() -> NodeTable {
+        NodeTable {
+            root: SceneNode::new(),
+            nodes: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn root(&self) -> &NodeRef {
+        &self.root
+    }
+
+    /// Attaches a node under the given parent, or the root. Returns
+    /// `None`, without attaching it, if the parent isn't in the table.
+    pub fn add(&mut self, parent: Option<ObjectId>, node: &NodeRef) -> Option<ObjectId> {
+        let parent = match parent {
+            Some(id) => self.nodes.get(&id)?.clone(),
+            None => self.root.clone(),
+        };
+        SceneNode::attach(&parent, node);
+
+        let id = ObjectId(self.next_id);
+        self.next_id += 1;
+        self.nodes.insert(id, node.clone());
+        Some(id)
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&NodeRef> {
+        self.nodes.get(&id)
+    }
+
+    /// Detaches a node, and drops it and everything under it from the
+    /// table.
+    pub fn remove(&mut self, id: ObjectId) -> Option<NodeRef> {
+        let node = self.nodes.remove(&id)?;
+        SceneNode::detach(&node);
+
+        let removed = SceneNode::descendants(&node);
+        self.nodes
+            .retain(|_, n| !removed.iter().any(|r| Rc::ptr_eq(r, n)));
+        Some(node)
+    }
+
+    /// The nodes in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectId, &NodeRef)> {
+        self.nodes.iter().map(|(id, node)| (*id, node))
+    }
+}
+
+impl Default for NodeTable {
+    fn default() -> NodeTable {
+        NodeTable::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use nalgebra::{Matrix4, Vector3};
+    use num::One;
+
+    use super::{NodeTable, SceneNode};
+
+    #[test]
+    fn attach_and_detach() {
+        let (a, b, child) = (SceneNode::new(), SceneNode::new(), SceneNode::new());
+
+        SceneNode::attach(&a, &child);
+        assert!(Rc::ptr_eq(&a, &child.borrow().parent().unwrap()));
+        assert_eq!(1, a.borrow().children().len());
+
+        // Attaching elsewhere moves it.
+        SceneNode::attach(&b, &child);
+        assert!(Rc::ptr_eq(&b, &child.borrow().parent().unwrap()));
+        assert!(a.borrow().children().is_empty());
+
+        SceneNode::detach(&child);
+        assert!(child.borrow().parent().is_none());
+        assert!(b.borrow().children().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn no_cycles() {
+        let (parent, child) = (SceneNode::new(), SceneNode::new());
+        SceneNode::attach(&parent, &child);
+        SceneNode::attach(&child, &parent);
+    }
+
+    #[test]
+    fn composed_transforms() {
+        let (root, arm, hand) = (SceneNode::new(), SceneNode::new(), SceneNode::new());
+        SceneNode::attach(&root, &arm);
+        SceneNode::attach(&arm, &hand);
+        root.borrow_mut().transform = Matrix4::new_scaling(2.0);
+        arm.borrow_mut().transform = Matrix4::new_translation(&Vector3::new(1.0, 0.0, 0.0));
+        hand.borrow_mut().transform = Matrix4::new_translation(&Vector3::new(0.0, 1.0, 0.0));
+
+        let expected =
+            Matrix4::new_scaling(2.0) * Matrix4::new_translation(&Vector3::new(1.0, 1.0, 0.0));
+        assert_eq!(expected, SceneNode::world_transform(&hand));
+
+        assert_eq!(3, SceneNode::descendants(&root).len());
+
+        let mut visited = vec![];
+        SceneNode::traverse(&root, &One::one(), &mut |_, transform| {
+            visited.push(*transform)
+        });
+        assert_eq!(3, visited.len());
+        assert_eq!(expected, visited[2]);
+
+        // Hiding the arm hides the hand too.
+        arm.borrow_mut().visible = false;
+        let mut count = 0;
+        SceneNode::traverse(&root, &One::one(), &mut |_, _| count += 1);
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    fn add_under_removed_parent() {
+        let mut table = NodeTable::new();
+        let parent = table.add(None, &SceneNode::new()).unwrap();
+        let child = table.add(Some(parent), &SceneNode::new()).unwrap();
+        assert!(table.remove(parent).is_some());
+
+        // Removing the parent removes its children too.
+        assert!(table.get(child).is_none());
+        assert!(table.remove(parent).is_none());
+
+        let orphan = SceneNode::new();
+        assert_eq!(None, table.add(Some(parent), &orphan));
+        assert!(orphan.borrow().parent().is_none());
+        assert!(table.root().borrow().children().is_empty());
+        assert_eq!(0, table.iter().count());
+    }
+}
@@ -0,0 +1,1212 @@
+//! A line-based, human-editable scene description. Each line starts
+//! with a keyword, followed by some positional arguments and then
+//! optional `key value...` pairs. For example:
+//!
+//! ```text
+//! camera position 0 0 70 looking_at 0 0 0 up 0 1 0
+//! ambient 1 1 1
+//! light key position 0 10 10 color 1 1 1 1
+//! light spot type spot position 0 30 0 direction 0 -1 0 cone 20 30 attenuation 1 0 0.001
+//! mesh bunny geometry/stanford_bunny.ply shader lit shininess 10
+//! mesh cube builtin:wireframe_cube shader unlit scale 10 10 10 line_width 2
+//! body bunny position 10 0 0 mesh bunny
+//! body origin fixed
+//! spring bunny origin 5
+//! ```
+//!
+//! Meshes are ply or obj files, or `builtin:wireframe_cube`, drawn
+//! with the `unlit`, `lit`, `phong`, `blinn_phong`, `flat` or
+//! `textured_lit` shader (which needs a `texture`), the
+//! `normal_mapped_lit` shader (which needs an obj file, a `texture` and
+//! a `normal_map`), or as a point cloud with the `points` shader and an
+//! optional `point_size`. Rotations are
+//! axis-angle vectors. A mesh's material comes from its `ambient`,
+//! `diffuse`, `specular`, `shininess`, `emissive` and
+//! `ignore_vertex_color` options. Lights are `point` (the default),
+//! `directional` or `spot` lights, with spot cone angles in degrees.
+//! Directional and spot lights with the `shadows` flag cast shadows,
+//! except from meshes with `no_cast_shadows` and onto meshes with
+//! `no_receive_shadows`. Bodies can follow a mesh, and fixed bodies
+//! anchor springs without being simulated.
+//!
+//! Names and paths with spaces or `#`s in them are double-quoted, with
+//! `\"` and `\\` escapes, e.g. `mesh "my bunny" "scans/bunny #2.ply"`.
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    rc::Rc,
+    str::FromStr,
+};
+
+use glium::{backend::Facade, Vertex};
+use nalgebra::{Point3, Rotation3, Vector3};
+
+use crate::{
+    camera::Camera,
+    geometry::{self, Geometry, PCNTTVertex, PCNTVertex},
+    material::Material,
+    mesh::Mesh,
+    physics::{Body, Spring, System},
+    point_cloud::PointCloud,
+    scene::{LightId, ObjectId, Scene, SceneNode},
+    shaders::{self, LightProperties, LightType, ProgramRegistry, ShaderSource},
+    texture,
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneDescription {
+    pub camera: Camera<f32>,
+
+    /// The scene's ambient light, if not the default.
+    pub ambient: Option<[f32; 3]>,
+    pub lights: Vec<LightDescription>,
+    pub meshes: Vec<MeshDescription>,
+    pub bodies: Vec<BodyDescription>,
+    pub springs: Vec<SpringDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightDescription {
+    pub name: String,
+    pub light_type: LightType,
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub color: [f32; 4],
+    pub attenuation: [f32; 3],
+
+    /// The inner and outer angles of a spot light's cone, in degrees.
+    pub cone: [f32; 2],
+    pub shadows: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ShaderChoice {
+    Unlit,
+    Lit,
+    Phong,
+    BlinnPhong,
+    Flat,
+    TexturedLit,
+    NormalMappedLit,
+    Points,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshDescription {
+    pub name: String,
+    pub source: String,
+    pub shader: ShaderChoice,
+    pub texture: Option<String>,
+    pub normal_map: Option<String>,
+    pub position: Point3<f32>,
+    pub scale: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub line_width: Option<f32>,
+    pub transparent: bool,
+    pub cast_shadows: bool,
+    pub receive_shadows: bool,
+    pub material: Material,
+
+    /// The size of the points, for the `points` shader.
+    pub point_size: Option<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BodyDescription {
+    pub name: String,
+    pub mass: f32,
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub fixed: bool,
+
+    /// The name of a mesh which follows the body around.
+    pub mesh: Option<String>,
+}
+
+/// A spring pulling `body` towards `anchor`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpringDescription {
+    pub body: String,
+    pub anchor: String,
+    pub constant: f32,
+}
+
+static BUILTIN_WIREFRAME_CUBE: &str = "builtin:wireframe_cube";
+
+impl FromStr for ShaderChoice {
+    type Err = io::Error;
+
+    fn from_str(string: &str) -> io::Result<ShaderChoice> {
+        match string {
+            "unlit" => Ok(ShaderChoice::Unlit),
+            "lit" => Ok(ShaderChoice::Lit),
+            "phong" => Ok(ShaderChoice::Phong),
+            "blinn_phong" => Ok(ShaderChoice::BlinnPhong),
+            "flat" => Ok(ShaderChoice::Flat),
+            "textured_lit" => Ok(ShaderChoice::TexturedLit),
+            "normal_mapped_lit" => Ok(ShaderChoice::NormalMappedLit),
+            "points" => Ok(ShaderChoice::Points),
+            _ => Err(other_io_error(&format!("Unknown shader: {:?}", string))),
+        }
+    }
+}
+
+impl ShaderChoice {
+    pub fn source(&self) -> &'static ShaderSource {
+        match *self {
+            ShaderChoice::Unlit => &shaders::UNLIT,
+            ShaderChoice::Lit => &shaders::LIT,
+            ShaderChoice::Phong => &shaders::PHONG_LIT,
+            ShaderChoice::BlinnPhong => &shaders::BLINN_PHONG_LIT,
+            ShaderChoice::Flat => &shaders::FLAT_LIT,
+            ShaderChoice::TexturedLit => &shaders::TEXTURED_LIT,
+            ShaderChoice::NormalMappedLit => &shaders::NORMAL_MAPPED_LIT,
+            ShaderChoice::Points => &shaders::POINTS,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            ShaderChoice::Unlit => "unlit",
+            ShaderChoice::Lit => "lit",
+            ShaderChoice::Phong => "phong",
+            ShaderChoice::BlinnPhong => "blinn_phong",
+            ShaderChoice::Flat => "flat",
+            ShaderChoice::TexturedLit => "textured_lit",
+            ShaderChoice::NormalMappedLit => "normal_mapped_lit",
+            ShaderChoice::Points => "points",
+        }
+    }
+}
+
+impl SceneDescription {
+    pub fn from_file(filename: &str) -> io::Result<SceneDescription> {
+        let file = File::open(filename)?;
+        Self::from_reader(file)
+    }
+
+    pub fn from_reader<T: Read>(reader: T) -> io::Result<SceneDescription> {
+        let file = BufReader::new(reader);
+        let mut rv = SceneDescription::default();
+
+        for line_result in file.lines() {
+            let owned_tokens = tokenize(&line_result?)?;
+            let tokens: Vec<&str> = owned_tokens.iter().map(String::as_str).collect();
+
+            match tokens.first() {
+                Some(&"camera") => {
+                    let options = Options::from_tokens(
+                        &tokens[1..],
+                        &[("position", 3), ("looking_at", 3), ("up", 3)],
+                    )?;
+                    let defaults = Camera::default();
+                    rv.camera = Camera::new(
+                        options.point("position")?.unwrap_or(defaults.position),
+                        options.point("looking_at")?.unwrap_or(defaults.looking_at),
+                        options.vector("up")?.unwrap_or(defaults.up),
+                    );
+                }
+                Some(&"ambient") => {
+                    let mut color = [0.0; 3];
+                    for (i, value) in color.iter_mut().enumerate() {
+                        *value = parse_positional(&tokens, i + 1, "ambient color")?;
+                    }
+                    if tokens.len() > 4 {
+                        return Err(other_io_error("Too many tokens for scene ambient"));
+                    }
+                    rv.ambient = Some(color);
+                }
+                Some(&"light") => {
+                    let name = parse_positional::<String>(&tokens, 1, "light name")?;
+                    check_unique("light", &name, rv.lights.iter().map(|l| &l.name))?;
+                    let options = Options::from_tokens(
+                        &tokens[2..],
+                        &[
+                            ("type", 1),
+                            ("position", 3),
+                            ("direction", 3),
+                            ("color", 4),
+                            ("attenuation", 3),
+                            ("cone", 2),
+                            ("shadows", 0),
+                        ],
+                    )?;
+                    rv.lights.push(LightDescription {
+                        name,
+                        light_type: match options.values.get("type") {
+                            Some(toks) => parse_light_type(toks[0])?,
+                            None => LightType::Point,
+                        },
+                        position: options.floats("position")?.unwrap_or([0.0; 3]),
+                        direction: options.floats("direction")?.unwrap_or([0.0, 0.0, -1.0]),
+                        color: options.floats("color")?.unwrap_or([1.0; 4]),
+                        attenuation: options.floats("attenuation")?.unwrap_or([1.0, 0.0, 0.0]),
+                        cone: options.floats("cone")?.unwrap_or([30.0, 30.0]),
+                        shadows: options.flag("shadows"),
+                    });
+                }
+                Some(&"mesh") => {
+                    let name = parse_positional::<String>(&tokens, 1, "mesh name")?;
+                    check_unique("mesh", &name, rv.meshes.iter().map(|m| &m.name))?;
+                    let source = parse_positional::<String>(&tokens, 2, "mesh source")?;
+                    let options = Options::from_tokens(
+                        &tokens[3..],
+                        &[
+                            ("shader", 1),
+                            ("texture", 1),
+                            ("normal_map", 1),
+                            ("position", 3),
+                            ("scale", 3),
+                            ("rotation", 3),
+                            ("line_width", 1),
+                            ("transparent", 0),
+                            ("no_cast_shadows", 0),
+                            ("no_receive_shadows", 0),
+                            ("ambient", 3),
+                            ("diffuse", 3),
+                            ("specular", 3),
+                            ("shininess", 1),
+                            ("emissive", 3),
+                            ("ignore_vertex_color", 0),
+                            ("point_size", 1),
+                        ],
+                    )?;
+                    let defaults = Material::default();
+                    rv.meshes.push(MeshDescription {
+                        name,
+                        source,
+                        shader: match options.values.get("shader") {
+                            Some(toks) => ShaderChoice::from_str(toks[0])?,
+                            None => ShaderChoice::Lit,
+                        },
+                        texture: options.parse("texture")?,
+                        normal_map: options.parse("normal_map")?,
+                        position: options.point("position")?.unwrap_or_else(Point3::origin),
+                        scale: options
+                            .vector("scale")?
+                            .unwrap_or_else(|| Vector3::repeat(1.0)),
+                        rotation: options.vector("rotation")?.unwrap_or_else(Vector3::zeros),
+                        line_width: options.float("line_width")?,
+                        transparent: options.flag("transparent"),
+                        cast_shadows: !options.flag("no_cast_shadows"),
+                        receive_shadows: !options.flag("no_receive_shadows"),
+                        material: Material {
+                            ambient: options.floats("ambient")?.unwrap_or(defaults.ambient),
+                            diffuse: options.floats("diffuse")?.unwrap_or(defaults.diffuse),
+                            specular: options.floats("specular")?.unwrap_or(defaults.specular),
+                            shininess: options.float("shininess")?.unwrap_or(defaults.shininess),
+                            emissive: options.floats("emissive")?.unwrap_or(defaults.emissive),
+                            use_vertex_color: !options.flag("ignore_vertex_color"),
+                        },
+                        point_size: options.float("point_size")?,
+                    });
+                }
+                Some(&"body") => {
+                    let name = parse_positional::<String>(&tokens, 1, "body name")?;
+                    check_unique("body", &name, rv.bodies.iter().map(|b| &b.name))?;
+                    let options = Options::from_tokens(
+                        &tokens[2..],
+                        &[
+                            ("mass", 1),
+                            ("position", 3),
+                            ("velocity", 3),
+                            ("fixed", 0),
+                            ("mesh", 1),
+                        ],
+                    )?;
+                    rv.bodies.push(BodyDescription {
+                        name,
+                        mass: options.float("mass")?.unwrap_or(1.0),
+                        position: options.vector("position")?.unwrap_or_else(Vector3::zeros),
+                        velocity: options.vector("velocity")?.unwrap_or_else(Vector3::zeros),
+                        fixed: options.flag("fixed"),
+                        mesh: options.parse("mesh")?,
+                    });
+                }
+                Some(&"spring") => {
+                    rv.springs.push(SpringDescription {
+                        body: parse_positional(&tokens, 1, "spring body")?,
+                        anchor: parse_positional(&tokens, 2, "spring anchor")?,
+                        constant: parse_positional(&tokens, 3, "spring constant")?,
+                    });
+                    if tokens.len() > 4 {
+                        return Err(other_io_error("Too many tokens for scene spring"));
+                    }
+                }
+                Some(keyword) => {
+                    return Err(other_io_error(&format!(
+                        "Unknown scene keyword: {:?}",
+                        keyword
+                    )))
+                }
+                None => {}
+            }
+        }
+
+        Ok(rv)
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let camera = &self.camera;
+        writeln!(
+            writer,
+            "camera position {} looking_at {} up {}",
+            join(camera.position.iter()),
+            join(camera.looking_at.iter()),
+            join(camera.up.iter())
+        )?;
+
+        if let Some(ambient) = self.ambient {
+            writeln!(writer, "ambient {}", join(ambient.iter()))?;
+        }
+
+        for light in self.lights.iter() {
+            write!(
+                writer,
+                "light {} type {} position {} direction {} color {} attenuation {} cone {}",
+                quote(&light.name),
+                light_type_name(light.light_type),
+                join(light.position.iter()),
+                join(light.direction.iter()),
+                join(light.color.iter()),
+                join(light.attenuation.iter()),
+                join(light.cone.iter())
+            )?;
+            if light.shadows {
+                write!(writer, " shadows")?;
+            }
+            writeln!(writer)?;
+        }
+
+        for mesh in self.meshes.iter() {
+            write!(
+                writer,
+                "mesh {} {} shader {}",
+                quote(&mesh.name),
+                quote(&mesh.source),
+                mesh.shader.name()
+            )?;
+            if let Some(ref texture) = mesh.texture {
+                write!(writer, " texture {}", quote(texture))?;
+            }
+            if let Some(ref normal_map) = mesh.normal_map {
+                write!(writer, " normal_map {}", quote(normal_map))?;
+            }
+            write!(
+                writer,
+                " position {} scale {} rotation {}",
+                join(mesh.position.iter()),
+                join(mesh.scale.iter()),
+                join(mesh.rotation.iter())
+            )?;
+            if let Some(line_width) = mesh.line_width {
+                write!(writer, " line_width {}", line_width)?;
+            }
+            if mesh.transparent {
+                write!(writer, " transparent")?;
+            }
+            if !mesh.cast_shadows {
+                write!(writer, " no_cast_shadows")?;
+            }
+            if !mesh.receive_shadows {
+                write!(writer, " no_receive_shadows")?;
+            }
+            let material = &mesh.material;
+            write!(
+                writer,
+                " ambient {} diffuse {} specular {} shininess {} emissive {}",
+                join(material.ambient.iter()),
+                join(material.diffuse.iter()),
+                join(material.specular.iter()),
+                material.shininess,
+                join(material.emissive.iter())
+            )?;
+            if !material.use_vertex_color {
+                write!(writer, " ignore_vertex_color")?;
+            }
+            if let Some(point_size) = mesh.point_size {
+                write!(writer, " point_size {}", point_size)?;
+            }
+            writeln!(writer)?;
+        }
+
+        for body in self.bodies.iter() {
+            write!(
+                writer,
+                "body {} mass {} position {} velocity {}",
+                quote(&body.name),
+                body.mass,
+                join(body.position.iter()),
+                join(body.velocity.iter())
+            )?;
+            if body.fixed {
+                write!(writer, " fixed")?;
+            }
+            if let Some(ref mesh) = body.mesh {
+                write!(writer, " mesh {}", quote(mesh))?;
+            }
+            writeln!(writer)?;
+        }
+
+        for spring in self.springs.iter() {
+            writeln!(
+                writer,
+                "spring {} {} {}",
+                quote(&spring.body),
+                quote(&spring.anchor),
+                spring.constant
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the meshes and creates the scene and the physics system.
+    pub fn build<F: Facade>(&self, facade: &F, width: u32, height: u32) -> io::Result<LoadedScene> {
+        let mut programs = ProgramRegistry::new();
+        let mut scene = Scene::new(facade, self.camera.clone(), width, height, &mut programs)
+            .map_err(|e| other_io_error(&e.to_string()))?;
+        if let Some(ambient) = self.ambient {
+            scene.set_ambient_light(ambient);
+        }
+        let mut lights = HashMap::new();
+        for desc in self.lights.iter() {
+            let id = scene
+                .add_light(desc.properties())
+                .map_err(|e| other_io_error(&e.to_string()))?;
+            lights.insert(desc.name.clone(), id);
+        }
+
+        let mut meshes = HashMap::new();
+        for desc in self.meshes.iter() {
+            let program = programs
+                .get(facade, desc.shader.source().name)
+                .map_err(|e| other_io_error(&e.to_string()))?;
+
+            let texture = match desc.texture {
+                Some(ref texture) => {
+                    Some(Rc::new(texture::load_texture(facade, texture).map_err(
+                        |e| other_io_error(&format!("Can't load texture {:?}: {}", texture, e)),
+                    )?))
+                }
+                None => None,
+            };
+            let normal_map = match desc.normal_map {
+                Some(ref normal_map) => Some(Rc::new(
+                    texture::load_normal_map(facade, normal_map).map_err(|e| {
+                        other_io_error(&format!("Can't load normal map {:?}: {}", normal_map, e))
+                    })?,
+                )),
+                None => None,
+            };
+            let needs = match desc.shader {
+                ShaderChoice::TexturedLit if texture.is_none() => Some("a texture"),
+                ShaderChoice::NormalMappedLit if texture.is_none() || normal_map.is_none() => {
+                    Some("a texture and a normal map")
+                }
+                _ => None,
+            };
+            if let Some(needs) = needs {
+                return Err(other_io_error(&format!(
+                    "Mesh {:?} uses {} without {}",
+                    desc.name,
+                    desc.shader.name(),
+                    needs
+                )));
+            }
+
+            let loaded = match desc.shader {
+                ShaderChoice::NormalMappedLit => {
+                    let loaded = if desc.source.ends_with(".obj") {
+                        geometry::load_normal_mapped_obj(facade, &desc.source)
+                    } else {
+                        Err(other_io_error("Only obj files can be normal mapped"))
+                    };
+                    let geometry = loaded.map_err(|e| {
+                        other_io_error(&format!("Can't load mesh {:?}: {}", desc.source, e))
+                    })?;
+                    let mut mesh = Mesh::new(Rc::new(geometry), program);
+                    desc.apply(&mut mesh);
+                    mesh.texture = texture;
+                    mesh.normal_map = normal_map;
+                    LoadedMesh::NormalMapped(Rc::new(RefCell::new(mesh)))
+                }
+                ShaderChoice::Points => {
+                    let geometry = load_geometry(facade, &desc.source)?;
+                    let mut cloud = PointCloud::new(Rc::new(geometry), program);
+                    desc.apply(&mut cloud.mesh);
+                    cloud.mesh.texture = texture;
+                    if let Some(point_size) = desc.point_size {
+                        cloud.point_size = point_size;
+                    }
+                    LoadedMesh::Points(Rc::new(RefCell::new(cloud)))
+                }
+                _ => {
+                    let geometry = load_geometry(facade, &desc.source)?;
+                    let mut mesh = Mesh::new(Rc::new(geometry), program);
+                    desc.apply(&mut mesh);
+                    mesh.texture = texture;
+                    LoadedMesh::Mesh(Rc::new(RefCell::new(mesh)))
+                }
+            };
+
+            let id = loaded.add_to(&mut scene);
+            scene.set_name(id, &desc.name);
+            meshes.insert(desc.name.clone(), loaded);
+        }
+
+        let mut system = System::new();
+        let mut bodies = HashMap::new();
+        for desc in self.bodies.iter() {
+            if let Some(ref mesh) = desc.mesh {
+                if !meshes.contains_key(mesh) {
+                    return Err(other_io_error(&format!(
+                        "Body {:?} follows unknown mesh {:?}",
+                        desc.name, mesh
+                    )));
+                }
+            }
+
+            let mut body = Body::new();
+            body.set_mass(desc.mass)
+                .set_position(desc.position)
+                .set_velocity(desc.velocity);
+            if !desc.fixed {
+                system.add_body(body.clone());
+            }
+            bodies.insert(desc.name.clone(), body);
+        }
+
+        for desc in self.springs.iter() {
+            let anchor = find_body(&bodies, &desc.anchor)?.clone();
+            let body = bodies
+                .get_mut(&desc.body)
+                .ok_or_else(|| other_io_error(&format!("Unknown body: {:?}", desc.body)))?;
+            body.add_constraint(Rc::new(Spring(desc.constant)), anchor);
+        }
+
+        Ok(LoadedScene {
+            scene,
+            system,
+            lights,
+            programs,
+            meshes,
+            bodies,
+            description: self.clone(),
+        })
+    }
+}
+
+impl MeshDescription {
+    // Sets up a mesh as described, apart from its textures.
+    fn apply<V: Vertex>(&self, mesh: &mut Mesh<V, u32>) {
+        mesh.position = self.position;
+        mesh.scale = self.scale;
+        mesh.orientation = Rotation3::new(self.rotation);
+        mesh.draw_state.line_width = self.line_width;
+        mesh.transparent = self.transparent;
+        mesh.cast_shadows = self.cast_shadows;
+        mesh.receive_shadows = self.receive_shadows;
+        mesh.material = self.material;
+    }
+
+    // Updates the description from a mesh's current state.
+    fn update_from<V: Vertex>(&mut self, mesh: &Mesh<V, u32>) {
+        self.position = mesh.position;
+        self.scale = mesh.scale;
+        self.rotation = mesh.orientation.scaled_axis();
+        self.line_width = mesh.draw_state.line_width;
+        self.transparent = mesh.transparent;
+        self.cast_shadows = mesh.cast_shadows;
+        self.receive_shadows = mesh.receive_shadows;
+        self.material = mesh.material;
+    }
+}
+
+fn load_geometry<F: Facade>(facade: &F, source: &str) -> io::Result<Geometry<PCNTVertex, u32>> {
+    let loaded = if source == BUILTIN_WIREFRAME_CUBE {
+        Ok(geometry::wireframe_cube(facade))
+    } else if source.ends_with(".ply") {
+        geometry::load_textured_ply(facade, source)
+    } else if source.ends_with(".obj") {
+        geometry::load_obj(facade, source)
+    } else {
+        return Err(other_io_error(&format!(
+            "Unknown mesh source: {:?}",
+            source
+        )));
+    };
+    loaded.map_err(|e| other_io_error(&format!("Can't load mesh {:?}: {}", source, e)))
+}
+
+impl LightDescription {
+    pub fn from_properties(name: &str, light: &LightProperties) -> LightDescription {
+        LightDescription {
+            name: name.to_string(),
+            light_type: light.light_type(),
+            position: light.position,
+            direction: light.direction,
+            color: light.color,
+            attenuation: light.attenuation,
+            cone: [
+                light.spot_inner_cos.acos().to_degrees(),
+                light.spot_outer_cos.acos().to_degrees(),
+            ],
+            shadows: light.casts_shadows,
+        }
+    }
+
+    pub fn properties(&self) -> LightProperties {
+        let [constant, linear, quadratic] = self.attenuation;
+        let mut light = match self.light_type {
+            LightType::Point => LightProperties::point(self.position, self.color),
+            LightType::Directional => LightProperties::directional(self.direction, self.color),
+            LightType::Spot => LightProperties::spot(
+                self.position,
+                self.direction,
+                self.cone[0].to_radians(),
+                self.cone[1].to_radians(),
+                self.color,
+            ),
+        }
+        .with_attenuation(constant, linear, quadratic);
+        light.casts_shadows = self.shadows;
+        light
+    }
+}
+
+/// A scene and physics system built from a description, along with
+/// its named parts.
+pub struct LoadedScene {
+    pub scene: Scene,
+    pub system: System,
+    pub lights: HashMap<String, LightId>,
+
+    /// The programs the scene and its meshes are drawn with.
+    pub programs: ProgramRegistry,
+    pub meshes: HashMap<String, LoadedMesh>,
+    pub bodies: HashMap<String, Body>,
+    description: SceneDescription,
+}
+
+/// A mesh from a scene description. Its vertex format and type depend
+/// on its shader.
+#[derive(Clone)]
+pub enum LoadedMesh {
+    Mesh(Rc<RefCell<Mesh<PCNTVertex, u32>>>),
+    NormalMapped(Rc<RefCell<Mesh<PCNTTVertex, u32>>>),
+    Points(Rc<RefCell<PointCloud<PCNTVertex, u32>>>),
+}
+
+impl LoadedMesh {
+    fn add_to(&self, scene: &mut Scene) -> ObjectId {
+        match self {
+            LoadedMesh::Mesh(mesh) => scene.add_object(mesh.clone()),
+            LoadedMesh::NormalMapped(mesh) => scene.add_object(mesh.clone()),
+            LoadedMesh::Points(cloud) => scene.add_object(cloud.clone()),
+        }
+    }
+
+    pub fn set_position(&self, position: Point3<f32>) {
+        match self {
+            LoadedMesh::Mesh(mesh) => mesh.borrow_mut().position = position,
+            LoadedMesh::NormalMapped(mesh) => mesh.borrow_mut().position = position,
+            LoadedMesh::Points(cloud) => cloud.borrow_mut().mesh.position = position,
+        }
+    }
+
+    fn describe(&self, desc: &mut MeshDescription) {
+        match self {
+            LoadedMesh::Mesh(mesh) => desc.update_from(&*mesh.borrow()),
+            LoadedMesh::NormalMapped(mesh) => desc.update_from(&*mesh.borrow()),
+            LoadedMesh::Points(cloud) => {
+                let cloud = cloud.borrow();
+                desc.update_from(&cloud.mesh);
+                desc.point_size = Some(cloud.point_size);
+            }
+        }
+    }
+
+    // The address of the object, to match it with the scene's.
+    fn object_address(&self) -> *const () {
+        match self {
+            LoadedMesh::Mesh(mesh) => Rc::as_ptr(mesh) as *const (),
+            LoadedMesh::NormalMapped(mesh) => Rc::as_ptr(mesh) as *const (),
+            LoadedMesh::Points(cloud) => Rc::as_ptr(cloud) as *const (),
+        }
+    }
+}
+
+pub fn load_scene<F: Facade>(
+    facade: &F,
+    filename: &str,
+    width: u32,
+    height: u32,
+) -> io::Result<LoadedScene> {
+    SceneDescription::from_file(filename)?.build(facade, width, height)
+}
+
+impl LoadedScene {
+    /// Runs the physics for the frame, and moves meshes to the bodies
+    /// they follow.
+    pub fn update(&mut self, frame_time: f32) {
+        let step_fraction = self.system.update(frame_time);
+        for desc in self.description.bodies.iter() {
+            if let Some(ref mesh) = desc.mesh {
+                let position = self.bodies[&desc.name].position(step_fraction);
+                self.meshes[mesh].set_position(position.into());
+            }
+        }
+    }
+
+    /// The description of the scene as it is now. Fails if objects
+    /// which the description can't represent, e.g. instanced meshes,
+    /// have been added to the scene.
+    pub fn describe(&self) -> io::Result<SceneDescription> {
+        for node in SceneNode::descendants(self.scene.root()) {
+            let node = node.borrow();
+            if let Some(object) = node.object() {
+                let address = Rc::as_ptr(object) as *const ();
+                if !self.meshes.values().any(|m| m.object_address() == address) {
+                    return Err(other_io_error(&format!(
+                        "Can't describe scene object {:?}, which isn't from the scene file",
+                        node.name.as_deref().unwrap_or("(unnamed)")
+                    )));
+                }
+            }
+        }
+
+        let mut rv = self.description.clone();
+        rv.camera = self.scene.camera.clone();
+
+        rv.lights = self
+            .description
+            .lights
+            .iter()
+            .filter_map(|desc| {
+                let light = self.scene.light(self.lights[&desc.name])?;
+                Some(LightDescription::from_properties(&desc.name, light))
+            })
+            .collect();
+
+        for desc in rv.meshes.iter_mut() {
+            self.meshes[&desc.name].describe(desc);
+        }
+
+        for desc in rv.bodies.iter_mut() {
+            let body = &self.bodies[&desc.name];
+            desc.mass = body.mass();
+            desc.position = body.position(1.0);
+            desc.velocity = body.velocity(1.0);
+        }
+
+        Ok(rv)
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        self.describe()?.save(filename)
+    }
+}
+
+fn find_body<'a>(bodies: &'a HashMap<String, Body>, name: &str) -> io::Result<&'a Body> {
+    bodies
+        .get(name)
+        .ok_or_else(|| other_io_error(&format!("Unknown body: {:?}", name)))
+}
+
+// The optional `key value...` pairs at the end of a line.
+struct Options<'a> {
+    values: HashMap<&'a str, &'a [&'a str]>,
+}
+
+impl<'a> Options<'a> {
+    fn from_tokens(toks: &'a [&'a str], known: &[(&str, usize)]) -> io::Result<Options<'a>> {
+        let mut values = HashMap::new();
+        let mut i = 0;
+        while i < toks.len() {
+            let count = known
+                .iter()
+                .find(|(key, _)| *key == toks[i])
+                .map(|(_, count)| *count)
+                .ok_or_else(|| other_io_error(&format!("Unknown scene option: {:?}", toks[i])))?;
+            if i + count >= toks.len() {
+                return Err(other_io_error(&format!(
+                    "Not enough values for scene option {:?}",
+                    toks[i]
+                )));
+            }
+            values.insert(toks[i], &toks[(i + 1)..(i + 1 + count)]);
+            i += 1 + count;
+        }
+        Ok(Options { values })
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    fn parse<T: FromStr>(&self, key: &str) -> io::Result<Option<T>>
+    where
+        T::Err: std::fmt::Debug,
+    {
+        self.values
+            .get(key)
+            .map(|toks| parse_token(toks[0], key))
+            .transpose()
+    }
+
+    fn float(&self, key: &str) -> io::Result<Option<f32>> {
+        self.parse(key)
+    }
+
+    fn floats<const N: usize>(&self, key: &str) -> io::Result<Option<[f32; N]>> {
+        match self.values.get(key) {
+            Some(toks) => {
+                let mut rv = [0.0; N];
+                for (value, tok) in rv.iter_mut().zip(toks.iter()) {
+                    *value = parse_token(tok, key)?;
+                }
+                Ok(Some(rv))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn vector(&self, key: &str) -> io::Result<Option<Vector3<f32>>> {
+        Ok(self.floats::<3>(key)?.map(Vector3::from))
+    }
+
+    fn point(&self, key: &str) -> io::Result<Option<Point3<f32>>> {
+        Ok(self.vector(key)?.map(Point3::from))
+    }
+}
+
+// Splits a line into tokens at whitespace, up to any `#` comment. A
+// token starting with a double quote runs to the closing quote.
+fn tokenize(line: &str) -> io::Result<Vec<String>> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.peek() {
+            None | Some('#') => break,
+            Some('"') => {
+                chars.next();
+                let mut token = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => token.push(c),
+                            _ => {
+                                return Err(other_io_error(&format!(
+                                    "Bad escape in scene string: {:?}",
+                                    line
+                                )))
+                            }
+                        },
+                        Some(c) => token.push(c),
+                        None => {
+                            return Err(other_io_error(&format!(
+                                "Unterminated scene string: {:?}",
+                                line
+                            )))
+                        }
+                    }
+                }
+                tokens.push(token);
+            }
+            Some(_) => {
+                let mut token = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '#') {
+                    token.push(c);
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// A name or path as a token that `tokenize` reads back unchanged.
+fn quote(string: &str) -> Cow<'_, str> {
+    let plain = !string.is_empty()
+        && !string.starts_with('"')
+        && !string.chars().any(|c| c.is_whitespace() || c == '#');
+    if plain {
+        Cow::Borrowed(string)
+    } else {
+        let escaped = string.replace('\\', "\\\\").replace('"', "\\\"");
+        Cow::Owned(format!("\"{}\"", escaped))
+    }
+}
+
+// Lights, meshes and bodies are looked up by name, so each name can
+// only be used once for each of them.
+fn check_unique<'a, I>(what: &str, name: &str, existing: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    if existing.into_iter().any(|other| other == name) {
+        return Err(other_io_error(&format!(
+            "Duplicate scene {} name: {:?}",
+            what, name
+        )));
+    }
+    Ok(())
+}
+
+fn parse_positional<T: FromStr>(toks: &[&str], index: usize, what: &str) -> io::Result<T>
+where
+    T::Err: std::fmt::Debug,
+{
+    let tok = toks
+        .get(index)
+        .ok_or_else(|| other_io_error(&format!("Missing scene {}", what)))?;
+    parse_token(tok, what)
+}
+
+fn parse_token<T: FromStr>(tok: &str, what: &str) -> io::Result<T>
+where
+    T::Err: std::fmt::Debug,
+{
+    T::from_str(tok).map_err(|e| {
+        other_io_error(&format!(
+            "Could not parse scene {} from {:?}: {:?}",
+            what, tok, e
+        ))
+    })
+}
+
+fn parse_light_type(string: &str) -> io::Result<LightType> {
+    match string {
+        "point" => Ok(LightType::Point),
+        "directional" => Ok(LightType::Directional),
+        "spot" => Ok(LightType::Spot),
+        _ => Err(other_io_error(&format!("Unknown light type: {:?}", string))),
+    }
+}
+
+fn light_type_name(light_type: LightType) -> &'static str {
+    match light_type {
+        LightType::Point => "point",
+        LightType::Directional => "directional",
+        LightType::Spot => "spot",
+    }
+}
+
+fn join<'a, I: Iterator<Item = &'a f32>>(values: I) -> String {
+    values
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn other_io_error(msg: &str) -> io::Error {
+    io::Error::other(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Point3, Vector3};
+
+    use super::{quote, tokenize, LightDescription, SceneDescription, ShaderChoice};
+    use crate::shaders::LightType;
+
+    static DEMO: &str = r"# The bunny on a spring, in a box.
+camera position 0 0 70 looking_at 0 0 0 up 0 1 0
+ambient 0.5 0.5 0.5
+light key position 0 10 10 color 1 1 1 1
+light spot type spot position 0 30 0 direction 0 -1 0 cone 20 30 attenuation 1 0.01 0 shadows
+
+mesh bunny geometry/stanford_bunny.ply shader lit shininess 20 specular 1 1 1
+mesh cube builtin:wireframe_cube shader unlit scale 10 10 10 line_width 2
+mesh ball sphere.obj shader blinn_phong
+mesh floor floor.obj shader textured_lit texture floor.png transparent ignore_vertex_color no_cast_shadows
+
+body bunny position 10 0 0 mesh bunny
+body origin fixed
+spring bunny origin 5
+";
+
+    fn error_description(doc_str: &str) -> String {
+        SceneDescription::from_reader(doc_str.as_bytes())
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn read_scene() {
+        let desc = SceneDescription::from_reader(DEMO.as_bytes()).unwrap();
+        assert_eq!(Point3::new(0.0, 0.0, 70.0), desc.camera.position);
+
+        assert_eq!(Some([0.5; 3]), desc.ambient);
+        assert_eq!(2, desc.lights.len());
+        assert_eq!("key", desc.lights[0].name);
+        assert_eq!([0.0, 10.0, 10.0], desc.lights[0].position);
+        assert_eq!(LightType::Point, desc.lights[0].light_type);
+        assert_eq!([1.0, 0.0, 0.0], desc.lights[0].attenuation);
+        assert_eq!(LightType::Spot, desc.lights[1].light_type);
+        assert_eq!([0.0, -1.0, 0.0], desc.lights[1].direction);
+        assert_eq!([20.0, 30.0], desc.lights[1].cone);
+        assert!(!desc.lights[0].shadows);
+        assert!(desc.lights[1].shadows);
+
+        let spot = desc.lights[1].properties();
+        assert_eq!(LightType::Spot, spot.light_type());
+        assert!(spot.spot_inner_cos > spot.spot_outer_cos);
+        assert!(spot.casts_shadows);
+        assert_eq!([1.0, 0.01, 0.0], spot.attenuation);
+
+        let described = LightDescription::from_properties("spot", &spot);
+        assert_eq!(LightType::Spot, described.light_type);
+        assert!((described.cone[0] - 20.0).abs() < 1e-3);
+        assert!((described.cone[1] - 30.0).abs() < 1e-3);
+
+        assert_eq!(4, desc.meshes.len());
+        assert_eq!(ShaderChoice::Lit, desc.meshes[0].shader);
+        assert_eq!(Vector3::repeat(1.0), desc.meshes[0].scale);
+        assert_eq!(Vector3::repeat(10.0), desc.meshes[1].scale);
+        assert_eq!(Some(2.0), desc.meshes[1].line_width);
+        assert_eq!(ShaderChoice::BlinnPhong, desc.meshes[2].shader);
+        assert_eq!(Some("floor.png".to_string()), desc.meshes[3].texture);
+        assert!(desc.meshes[3].transparent);
+        assert!(desc.meshes[0].cast_shadows);
+        assert!(!desc.meshes[3].cast_shadows);
+        assert!(desc.meshes[3].receive_shadows);
+
+        assert_eq!(20.0, desc.meshes[0].material.shininess);
+        assert_eq!([1.0; 3], desc.meshes[0].material.specular);
+        assert_eq!([0.7; 3], desc.meshes[0].material.diffuse);
+        assert!(desc.meshes[0].material.use_vertex_color);
+        assert!(!desc.meshes[3].material.use_vertex_color);
+
+        assert_eq!(2, desc.bodies.len());
+        assert_eq!(Vector3::new(10.0, 0.0, 0.0), desc.bodies[0].position);
+        assert_eq!(Some("bunny".to_string()), desc.bodies[0].mesh);
+        assert!(desc.bodies[1].fixed);
+
+        assert_eq!(1, desc.springs.len());
+        assert_eq!(5.0, desc.springs[0].constant);
+    }
+
+    #[test]
+    fn normal_mapped_and_point_meshes() {
+        let desc = SceneDescription::from_reader(
+            "mesh wall wall.obj shader normal_mapped_lit texture wall.png normal_map wall_normals.png\nmesh scan scan.ply shader points point_size 4\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(ShaderChoice::NormalMappedLit, desc.meshes[0].shader);
+        assert_eq!(
+            Some("wall_normals.png".to_string()),
+            desc.meshes[0].normal_map
+        );
+        assert_eq!(ShaderChoice::Points, desc.meshes[1].shader);
+        assert_eq!(Some(4.0), desc.meshes[1].point_size);
+        assert_eq!(None, desc.meshes[0].point_size);
+
+        let mut written = vec![];
+        desc.write(&mut written).unwrap();
+        assert_eq!(desc, SceneDescription::from_reader(&written[..]).unwrap());
+    }
+
+    #[test]
+    fn demo_scene() {
+        let desc = SceneDescription::from_file("scenes/demo.scene").unwrap();
+        assert_eq!(2, desc.meshes.len());
+        assert_eq!(2, desc.bodies.len());
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let desc = SceneDescription::from_reader(DEMO.as_bytes()).unwrap();
+        let mut written = vec![];
+        desc.write(&mut written).unwrap();
+        assert_eq!(desc, SceneDescription::from_reader(&written[..]).unwrap());
+    }
+
+    #[test]
+    fn quoted_strings() {
+        assert_eq!(
+            vec!["mesh", "my bunny", "a \"b\" \\ #c", "", "x"],
+            tokenize(r#"  mesh "my bunny" "a \"b\" \\ #c" "" x # comment"#).unwrap()
+        );
+        for string in ["plain", r"C:\scans\bunny.ply", "two words", "#1", "\"", ""].iter() {
+            assert_eq!(vec![string.to_string()], tokenize(&quote(string)).unwrap());
+        }
+        assert_eq!("plain", quote("plain"));
+        assert!(tokenize(r#"mesh "open"#).is_err());
+        assert!(tokenize(r#"mesh "\n""#).is_err());
+
+        let desc = SceneDescription::from_reader(
+            r#"light "key light"
+mesh "my bunny" "scans/bunny #2.ply" texture "fur texture.png"
+body "a body" mesh "my bunny"
+body origin fixed
+spring "a body" origin 5
+"#
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!("key light", desc.lights[0].name);
+        assert_eq!("scans/bunny #2.ply", desc.meshes[0].source);
+        assert_eq!(Some("fur texture.png".to_string()), desc.meshes[0].texture);
+        assert_eq!(Some("my bunny".to_string()), desc.bodies[0].mesh);
+        assert_eq!("a body", desc.springs[0].body);
+
+        let mut written = vec![];
+        desc.write(&mut written).unwrap();
+        assert_eq!(desc, SceneDescription::from_reader(&written[..]).unwrap());
+    }
+
+    #[test]
+    fn bad_data() {
+        assert_eq!(
+            "Unknown scene keyword: \"puppy\"",
+            error_description("puppy 1 2 3\n")
+        );
+        assert_eq!(
+            "Unknown scene option: \"colour\"",
+            error_description("light 0 colour 1 1 1 1\n")
+        );
+        assert_eq!(
+            "Not enough values for scene option \"position\"",
+            error_description("camera position 0 0\n")
+        );
+        assert_eq!(
+            "Could not parse scene mass from \"heavy\": ParseFloatError { kind: Invalid }",
+            error_description("body b mass heavy\n")
+        );
+        assert_eq!(
+            "Unknown shader: \"toon\"",
+            error_description("mesh m m.obj shader toon\n")
+        );
+        assert_eq!("Missing scene mesh source", error_description("mesh m\n"));
+        assert_eq!(
+            "Unknown light type: \"area\"",
+            error_description("light 0 type area\n")
+        );
+        assert_eq!(
+            "Duplicate scene light name: \"key\"",
+            error_description("light key\nlight fill\nlight key\n")
+        );
+        assert_eq!(
+            "Duplicate scene mesh name: \"m\"",
+            error_description("mesh m a.obj\nmesh m b.obj\n")
+        );
+        assert_eq!(
+            "Duplicate scene body name: \"b\"",
+            error_description("body b\nbody b fixed\n")
+        );
+    }
+}
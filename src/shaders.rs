@@ -1,29 +1,302 @@
+use std::{error::Error, fmt};
+
 use glium::backend::Facade;
+use glium::program::{ProgramCreationError, ProgramCreationInput, ShaderType};
+use glium::uniforms::{AsUniformValue, UniformValue, Uniforms};
 use glium::Program;
 use nalgebra::*;
 
-pub fn unlit<F: Facade>(facade: &F) -> Program {
-    Program::from_source(facade, UNLIT.vertex, UNLIT.fragment, None).unwrap()
+pub use self::registry::ProgramRegistry;
+pub use self::reload::ShaderReloader;
+
+pub mod preprocess;
+pub mod registry;
+pub mod reload;
+
+pub fn unlit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    UNLIT.build(facade)
+}
+
+pub fn lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    LIT.build(facade)
+}
+
+/// Lit per fragment, with Phong specular highlights.
+pub fn phong_lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    PHONG_LIT.build(facade)
+}
+
+/// Lit per fragment, with Blinn-Phong specular highlights.
+pub fn blinn_phong_lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    BLINN_PHONG_LIT.build(facade)
+}
+
+/// Lit per fragment with a single normal for each triangle, ignoring
+/// the vertex normals.
+pub fn flat_lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    FLAT_LIT.build(facade)
+}
+
+pub fn textured_lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    TEXTURED_LIT.build(facade)
+}
+
+pub fn normal_mapped_lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    NORMAL_MAPPED_LIT.build(facade)
+}
+
+pub fn instanced_unlit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    INSTANCED_UNLIT.build(facade)
+}
+
+pub fn instanced_lit<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    INSTANCED_LIT.build(facade)
 }
 
-pub fn lit<F: Facade>(facade: &F) -> Program {
-    Program::from_source(facade, LIT.vertex, LIT.fragment, None).unwrap()
+/// Only writes depth, for rendering shadow maps.
+pub fn shadow<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    SHADOW.build(facade)
 }
 
-struct ShaderSource {
+pub fn instanced_shadow<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    INSTANCED_SHADOW.build(facade)
+}
+
+pub fn points<F: Facade>(facade: &F) -> Result<Program, ShaderError> {
+    POINTS.build(facade)
+}
+
+/// The GLSL for a program, built in, along with the names of the
+/// files in `src/shaders` it came from.
+pub struct ShaderSource {
+    /// The program's name in a `ProgramRegistry`.
+    pub name: &'static str,
     pub vertex: &'static str,
     pub fragment: &'static str,
+    pub vertex_file: &'static str,
+    pub fragment_file: &'static str,
+
+    /// Whether the vertex shader sets `gl_PointSize` itself.
+    pub uses_point_size: bool,
+}
+
+macro_rules! shader_source {
+    ($name:literal, $vertex:literal, $fragment:literal) => {
+        shader_source!($name, $vertex, $fragment, false)
+    };
+    ($name:literal, $vertex:literal, $fragment:literal, $uses_point_size:literal) => {
+        ShaderSource {
+            name: $name,
+            vertex: include_str!(concat!("shaders/", $vertex)),
+            fragment: include_str!(concat!("shaders/", $fragment)),
+            vertex_file: $vertex,
+            fragment_file: $fragment,
+            uses_point_size: $uses_point_size,
+        }
+    };
+}
+
+impl ShaderSource {
+    pub fn build<F: Facade>(&self, facade: &F) -> Result<Program, ShaderError> {
+        self.build_from(facade, self.vertex, self.fragment, built_in_include)
+    }
+
+    /// Builds the program from other versions of its shaders, e.g.
+    /// ones read from disk, looking up the files they include with
+    /// `include`.
+    pub fn build_from<F, I>(
+        &self,
+        facade: &F,
+        vertex: &str,
+        fragment: &str,
+        include: I,
+    ) -> Result<Program, ShaderError>
+    where
+        F: Facade,
+        I: Fn(&str) -> Option<String>,
+    {
+        let defines = defines();
+        let vertex = preprocess::preprocess(vertex, &include, &defines)?;
+        let fragment = preprocess::preprocess(fragment, &include, &defines)?;
+        let input = ProgramCreationInput::SourceCode {
+            vertex_shader: &vertex.source,
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            geometry_shader: None,
+            fragment_shader: &fragment.source,
+            transform_feedback_varyings: None,
+            outputs_srgb: false,
+            uses_point_size: self.uses_point_size,
+        };
+        Program::new(facade, input).map_err(|error| {
+            let includes = match error {
+                ProgramCreationError::CompilationError(_, ShaderType::Vertex) => vertex.includes,
+                ProgramCreationError::CompilationError(_, ShaderType::Fragment) => {
+                    fragment.includes
+                }
+                _ => vec![],
+            };
+            ShaderError::Build {
+                program: self.name,
+                error,
+                includes,
+            }
+        })
+    }
+}
+
+/// Why a program couldn't be built.
+#[derive(Debug)]
+pub enum ShaderError {
+    /// There's no built-in program with the name.
+    UnknownProgram(String),
+
+    /// An included file is missing, or an `#include` is malformed.
+    Preprocess(String),
+
+    /// The GL couldn't compile or link the program. The error holds
+    /// its info log, and `includes` are the files its source string
+    /// numbers after 0 refer to.
+    Build {
+        program: &'static str,
+        error: ProgramCreationError,
+        includes: Vec<String>,
+    },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::UnknownProgram(name) => write!(f, "Unknown program: {:?}", name),
+            ShaderError::Preprocess(message) => write!(f, "{}", message),
+            ShaderError::Build {
+                program,
+                error,
+                includes,
+            } => {
+                write!(f, "Error building the {} program: {}", program, error)?;
+                for (i, file) in includes.iter().enumerate() {
+                    write!(f, "\nSource string {} is {}", i + 1, file)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for ShaderError {}
+
+/// The files in `src/shaders` the built-in shaders can include.
+static INCLUDES: [(&str, &str); 5] = [
+    ("lighting.glsl", include_str!("shaders/lighting.glsl")),
+    ("lights.glsl", include_str!("shaders/lights.glsl")),
+    ("material.glsl", include_str!("shaders/material.glsl")),
+    ("shadows.glsl", include_str!("shaders/shadows.glsl")),
+    (
+        "view_and_projection.glsl",
+        include_str!("shaders/view_and_projection.glsl"),
+    ),
+];
+
+fn built_in_include(name: &str) -> Option<String> {
+    INCLUDES
+        .iter()
+        .find(|(file, _)| *file == name)
+        .map(|(_, text)| text.to_string())
+}
+
+/// The constants the shaders share with the Rust side, defined at the
+/// top of each one.
+fn defines() -> Vec<(&'static str, String)> {
+    vec![
+        ("MAX_LIGHTS", MAX_LIGHTS.to_string()),
+        ("POINT_LIGHT", (LightType::Point as i32).to_string()),
+        (
+            "DIRECTIONAL_LIGHT",
+            (LightType::Directional as i32).to_string(),
+        ),
+        ("SPOT_LIGHT", (LightType::Spot as i32).to_string()),
+    ]
+}
+
+/// The built-in program called `name`.
+pub fn source(name: &str) -> Option<&'static ShaderSource> {
+    SOURCES.iter().find(|source| source.name == name).copied()
 }
 
-static UNLIT: ShaderSource = ShaderSource {
-    vertex: include_str!("shaders/unlit_vertex.glsl"),
-    fragment: include_str!("shaders/unlit_fragment.glsl"),
-};
+pub static SOURCES: [&ShaderSource; 12] = [
+    &UNLIT,
+    &LIT,
+    &PHONG_LIT,
+    &BLINN_PHONG_LIT,
+    &FLAT_LIT,
+    &TEXTURED_LIT,
+    &NORMAL_MAPPED_LIT,
+    &INSTANCED_UNLIT,
+    &INSTANCED_LIT,
+    &SHADOW,
+    &INSTANCED_SHADOW,
+    &POINTS,
+];
+
+pub static UNLIT: ShaderSource =
+    shader_source!("unlit", "unlit_vertex.glsl", "unlit_fragment.glsl");
+
+pub static LIT: ShaderSource = shader_source!("lit", "lit_vertex.glsl", "lit_fragment.glsl");
+
+pub static PHONG_LIT: ShaderSource = shader_source!(
+    "phong_lit",
+    "per_fragment_lit_vertex.glsl",
+    "phong_fragment.glsl"
+);
+
+pub static BLINN_PHONG_LIT: ShaderSource = shader_source!(
+    "blinn_phong_lit",
+    "per_fragment_lit_vertex.glsl",
+    "blinn_phong_fragment.glsl"
+);
+
+pub static FLAT_LIT: ShaderSource = shader_source!(
+    "flat_lit",
+    "per_fragment_lit_vertex.glsl",
+    "flat_fragment.glsl"
+);
+
+pub static TEXTURED_LIT: ShaderSource = shader_source!(
+    "textured_lit",
+    "textured_lit_vertex.glsl",
+    "textured_lit_fragment.glsl"
+);
+
+pub static NORMAL_MAPPED_LIT: ShaderSource = shader_source!(
+    "normal_mapped_lit",
+    "normal_mapped_lit_vertex.glsl",
+    "normal_mapped_lit_fragment.glsl"
+);
+
+pub static INSTANCED_UNLIT: ShaderSource = shader_source!(
+    "instanced_unlit",
+    "instanced_unlit_vertex.glsl",
+    "unlit_fragment.glsl"
+);
+
+pub static INSTANCED_LIT: ShaderSource = shader_source!(
+    "instanced_lit",
+    "instanced_lit_vertex.glsl",
+    "lit_fragment.glsl"
+);
 
-static LIT: ShaderSource = ShaderSource {
-    vertex: include_str!("shaders/lit_vertex.glsl"),
-    fragment: include_str!("shaders/lit_fragment.glsl"),
-};
+pub static SHADOW: ShaderSource =
+    shader_source!("shadow", "shadow_vertex.glsl", "shadow_fragment.glsl");
+
+pub static INSTANCED_SHADOW: ShaderSource = shader_source!(
+    "instanced_shadow",
+    "instanced_shadow_vertex.glsl",
+    "shadow_fragment.glsl"
+);
+
+pub static POINTS: ShaderSource =
+    shader_source!("points", "points_vertex.glsl", "points_fragment.glsl", true);
 
 #[derive(Clone, Copy, Debug)]
 pub struct ViewAndProjectionBlock {
@@ -34,62 +307,257 @@ pub struct ViewAndProjectionBlock {
 
 implement_uniform_block!(ViewAndProjectionBlock, view, view_inv, projection);
 
+/// The kinds of light, as `LightProperties::light_type` in the
+/// shaders.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LightType {
+    /// Shines in all directions from a position.
+    Point = 0,
+
+    /// Shines in a single direction from infinitely far away, like the
+    /// sun.
+    Directional = 1,
+
+    /// Shines in a cone from a position.
+    Spot = 2,
+}
+
 #[derive(Clone, Copy, Debug)]
+#[repr(C)]
 pub struct LightProperties {
-    pub enabled: bool,
-    #[allow(dead_code)]
-    padding1: [u32; 3],
     pub position: [f32; 3],
-    #[allow(dead_code)]
-    padding2: u32,
+    pub enabled: bool,
+
+    /// The direction directional and spot lights shine in.
+    pub direction: [f32; 3],
+    light_type: i32,
     pub color: [f32; 4],
-    pub specular_exp: f32,
+
+    /// The constant, linear and quadratic coefficients of the distance
+    /// in the light's falloff, `1 / (c + l * d + q * d^2)`. Directional
+    /// lights don't fall off.
+    pub attenuation: [f32; 3],
+
+    /// The cosines of the angles from the axis of a spot light within
+    /// which it's at full intensity, and beyond which it's dark.
+    pub spot_inner_cos: f32,
+    pub spot_outer_cos: f32,
+
+    /// Whether objects block this light. Only directional and spot
+    /// lights cast shadows, and only `MAX_SHADOW_MAPS` of them at
+    /// once.
+    pub casts_shadows: bool,
+
+    // The index of the light's shadow map, or -1 for none, and the
+    // transformation from world space to the map's clip space. The
+    // scene assigns these when it renders.
+    shadow_map: i32,
     #[allow(dead_code)]
-    padding3: [u32; 3],
+    padding1: u32,
+    light_space: [[f32; 4]; 4],
 }
 
 impl LightProperties {
-    pub fn new(
-        enabled: bool,
+    pub fn point(position: [f32; 3], color: [f32; 4]) -> LightProperties {
+        LightProperties {
+            enabled: true,
+            position,
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn directional(direction: [f32; 3], color: [f32; 4]) -> LightProperties {
+        LightProperties {
+            enabled: true,
+            direction,
+            light_type: LightType::Directional as i32,
+            color,
+            ..Default::default()
+        }
+    }
+
+    /// A spot light, with the angles of its cone in radians.
+    pub fn spot(
         position: [f32; 3],
+        direction: [f32; 3],
+        inner_angle: f32,
+        outer_angle: f32,
         color: [f32; 4],
-        specular_exp: f32,
     ) -> LightProperties {
         LightProperties {
-            enabled: enabled,
-            position: position,
-            color: color,
-            specular_exp: specular_exp,
+            enabled: true,
+            position,
+            direction,
+            light_type: LightType::Spot as i32,
+            color,
+            spot_inner_cos: inner_angle.cos(),
+            spot_outer_cos: outer_angle.cos(),
             ..Default::default()
         }
     }
+
+    pub fn with_attenuation(self, constant: f32, linear: f32, quadratic: f32) -> LightProperties {
+        LightProperties {
+            attenuation: [constant, linear, quadratic],
+            ..self
+        }
+    }
+
+    pub fn with_shadows(self) -> LightProperties {
+        LightProperties {
+            casts_shadows: true,
+            ..self
+        }
+    }
+
+    pub fn shadow_map(&self) -> Option<usize> {
+        if self.shadow_map < 0 {
+            None
+        } else {
+            Some(self.shadow_map as usize)
+        }
+    }
+
+    pub(crate) fn with_shadow_map(
+        self,
+        shadow_map: Option<(usize, Matrix4<f32>)>,
+    ) -> LightProperties {
+        match shadow_map {
+            Some((index, light_space)) => LightProperties {
+                shadow_map: index as i32,
+                light_space: *light_space.as_ref(),
+                ..self
+            },
+            None => LightProperties {
+                shadow_map: -1,
+                ..self
+            },
+        }
+    }
+
+    pub fn light_type(&self) -> LightType {
+        match self.light_type {
+            1 => LightType::Directional,
+            2 => LightType::Spot,
+            _ => LightType::Point,
+        }
+    }
 }
 
 impl Default for LightProperties {
     fn default() -> LightProperties {
         LightProperties {
-            enabled: false,
             position: [0.0, 0.0, 0.0],
+            enabled: false,
+            direction: [0.0, 0.0, -1.0],
+            light_type: LightType::Point as i32,
             color: [0.0, 0.0, 0.0, 1.0],
-            specular_exp: 0.0,
+            attenuation: [1.0, 0.0, 0.0],
+            spot_inner_cos: 1.0,
+            spot_outer_cos: 1.0,
+            casts_shadows: false,
+            shadow_map: -1,
+            light_space: *Matrix4::identity().as_ref(),
 
-            padding2: Default::default(),
             padding1: Default::default(),
-            padding3: Default::default(),
         }
     }
 }
 
-implement_uniform_block!(LightProperties, position, specular_exp, color, enabled);
+implement_uniform_block!(
+    LightProperties,
+    position,
+    enabled,
+    direction,
+    light_type,
+    color,
+    attenuation,
+    spot_inner_cos,
+    spot_outer_cos,
+    casts_shadows,
+    shadow_map,
+    light_space
+);
 
 pub const MAX_LIGHTS: usize = 10;
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug)]
 pub struct LightListBlock {
     pub lights: [LightProperties; MAX_LIGHTS],
+
+    /// Lights every surface evenly, multiplied with the ambient color
+    /// of its material.
+    pub ambient: [f32; 3],
 }
 
-implement_uniform_block!(LightListBlock, lights);
+impl Default for LightListBlock {
+    fn default() -> LightListBlock {
+        LightListBlock {
+            lights: Default::default(),
+            ambient: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+implement_uniform_block!(LightListBlock, lights, ambient);
+
+pub const MAX_SHADOW_MAPS: usize = 4;
+
+/// The std140 layout of a `Material`.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct MaterialProperties {
+    pub ambient: [f32; 3],
+    pub shininess: f32,
+    pub diffuse: [f32; 3],
+    pub use_vertex_color: bool,
+    pub specular: [f32; 3],
+    #[allow(dead_code)]
+    padding1: u32,
+    pub emissive: [f32; 3],
+    #[allow(dead_code)]
+    padding2: u32,
+}
+
+impl MaterialProperties {
+    pub fn new(
+        ambient: [f32; 3],
+        diffuse: [f32; 3],
+        specular: [f32; 3],
+        shininess: f32,
+        emissive: [f32; 3],
+        use_vertex_color: bool,
+    ) -> MaterialProperties {
+        MaterialProperties {
+            ambient,
+            shininess,
+            diffuse,
+            use_vertex_color,
+            specular,
+            padding1: Default::default(),
+            emissive,
+            padding2: Default::default(),
+        }
+    }
+}
+
+implement_uniform_block!(
+    MaterialProperties,
+    ambient,
+    shininess,
+    diffuse,
+    use_vertex_color,
+    specular,
+    emissive
+);
+
+#[derive(Clone, Copy, Debug)]
+pub struct MaterialBlock {
+    pub material: MaterialProperties,
+}
+
+implement_uniform_block!(MaterialBlock, material);
 
 #[derive(Clone, Copy, Debug)]
 pub struct ModelTransformation {
@@ -97,6 +565,23 @@ pub struct ModelTransformation {
     pub model_normal: Matrix3<f32>,
 }
 
+impl ModelTransformation {
+    /// The transformation for a model matrix, with the normal matrix
+    /// as the inverse transpose of its upper-left 3x3, which keeps
+    /// normals perpendicular to their surfaces under non-uniform
+    /// scaling.
+    pub fn from_model(model: Matrix4<f32>) -> ModelTransformation {
+        let upper_left: Matrix3<f32> = model.fixed_slice::<U3, U3>(0, 0).into_owned();
+        ModelTransformation {
+            model,
+            model_normal: upper_left
+                .try_inverse()
+                .map(|m| m.transpose())
+                .unwrap_or_else(one),
+        }
+    }
+}
+
 impl Default for ModelTransformation {
     fn default() -> ModelTransformation {
         ModelTransformation {
@@ -105,3 +590,52 @@ impl Default for ModelTransformation {
         }
     }
 }
+
+/// Uniforms specific to a single scene object, which the scene passes
+/// to the object's program along with its own (the model transform,
+/// the view and projection, and the lights).
+#[derive(Default)]
+pub struct ObjectUniforms<'a> {
+    values: Vec<(&'static str, Box<dyn AsUniformValue + 'a>)>,
+}
+
+impl<'a> ObjectUniforms<'a> {
+    pub fn new() -> ObjectUniforms<'a> {
+        Default::default()
+    }
+
+    pub fn add<U: AsUniformValue + 'a>(
+        mut self,
+        name: &'static str,
+        value: U,
+    ) -> ObjectUniforms<'a> {
+        self.values.push((name, Box::new(value)));
+        self
+    }
+}
+
+impl<'a> Uniforms for ObjectUniforms<'a> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut output: F) {
+        for (name, value) in self.values.iter() {
+            output(name, value.as_uniform_value());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{built_in_include, defines, preprocess::preprocess, source, SOURCES};
+
+    #[test]
+    fn built_in_sources_preprocess() {
+        for (i, built_in) in SOURCES.iter().enumerate() {
+            assert!(std::ptr::eq(source(built_in.name).unwrap(), *built_in));
+            assert!(SOURCES[..i].iter().all(|other| other.name != built_in.name));
+            for shader in [built_in.vertex, built_in.fragment].iter() {
+                let rv = preprocess(shader, built_in_include, &defines()).unwrap();
+                assert!(!rv.source.contains("#include"));
+            }
+        }
+        assert!(source("nothing").is_none());
+    }
+}
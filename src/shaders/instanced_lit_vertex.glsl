@@ -0,0 +1,40 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "view_and_projection.glsl"
+
+in vec3 position;
+in vec3 normal;
+in vec4 color;
+
+// Per instance.
+in mat4x4 instance_model;
+in mat3x3 instance_model_normal;
+in vec4 instance_color;
+
+uniform mat4x4 model;
+uniform mat3x3 model_normal;
+
+out vec3 v_position;
+out vec3 v_normal;
+out vec4 v_color;
+out vec3 v_eye_dir;
+
+void main(void) {
+    vec4 wld_vert_position4 = model * instance_model * vec4(position, 1.0);
+    vec3 wld_vert_position = wld_vert_position4.xyz / wld_vert_position4.w;
+
+    vec4 wld_eye_position4 = view_inv * vec4(0.0, 0.0, 0.0, 1.0);
+    vec3 wld_eye_position = wld_eye_position4.xyz / wld_eye_position4.w;
+
+    vec3 wld_vert_normal = normalize(model_normal * instance_model_normal * normal);
+
+    vec3 wld_vert_eye_dir = normalize(wld_eye_position - wld_vert_position);
+
+    gl_Position = projection * view * wld_vert_position4;
+    v_color = color * instance_color;
+    v_position = wld_vert_position;
+    v_eye_dir = wld_vert_eye_dir;
+    v_normal = wld_vert_normal;
+}
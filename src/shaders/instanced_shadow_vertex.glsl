@@ -0,0 +1,15 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+in vec3 position;
+
+// Per instance.
+in mat4x4 instance_model;
+
+uniform mat4x4 model;
+uniform mat4x4 light_space;
+
+void main(void) {
+    gl_Position = light_space * model * instance_model * vec4(position, 1.0);
+}
@@ -0,0 +1,21 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "view_and_projection.glsl"
+
+in vec3 position;
+in vec4 color;
+
+// Per instance.
+in mat4x4 instance_model;
+in vec4 instance_color;
+
+uniform mat4x4 model;
+
+out vec4 v_color;
+
+void main(void) {
+    gl_Position = projection * view * model * instance_model * vec4(position, 1.0);
+    v_color = color * instance_color;
+}
@@ -0,0 +1,44 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+// The lighting model shared by the lit fragment shaders. The specular
+// term uses the reflection of the light direction, or the half vector
+// if BLINN_PHONG is defined before this is included.
+
+#include "lights.glsl"
+#include "material.glsl"
+#include "shadows.glsl"
+
+// The color of a fragment at `position` with the given unit normal
+// and direction to the eye, lit by all of the scene's lights.
+vec3 shade(vec3 normal, vec3 eye_dir, vec3 position, vec3 base_color) {
+    vec3 color = material.emissive + material.ambient * base_color * ambient;
+
+    for (int i = 0; i < MAX_LIGHTS; ++i) {
+        if (lights[i].enabled) {
+            vec3 light_dir = lights[i].light_type == DIRECTIONAL_LIGHT
+                ? normalize(-lights[i].direction)
+                : normalize(lights[i].position - position);
+            vec3 light_color = light_intensity(lights[i], position)
+                * light_visibility(lights[i], position) * lights[i].color.rgb;
+
+            float diffuse_coeff = max(0.0, dot(normal, light_dir));
+            vec3 diffuse_color = diffuse_coeff * material.diffuse * base_color * light_color;
+
+            vec3 specular_color = vec3(0.0, 0.0, 0.0);
+            if (dot(normal, light_dir) >= 0.0) {
+#ifdef BLINN_PHONG
+                // The half vector gives broader highlights than the
+                // reflection for the same shininess.
+                vec3 half_dir = normalize(light_dir + eye_dir);
+                float spec_coeff = pow(max(0.0, dot(normal, half_dir)), material.shininess);
+#else
+                float spec_coeff = pow(max(0.0, dot(reflect(-light_dir, normal), eye_dir)), material.shininess);
+#endif
+                specular_color = spec_coeff * material.specular * light_color;
+            }
+
+            color = clamp(color + clamp(diffuse_color + specular_color, 0.0, 1.0), 0.0, 1.0);
+        }
+    }
+    return color;
+}
@@ -0,0 +1,41 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+// The scene's lights. MAX_LIGHTS and the light types (POINT_LIGHT,
+// DIRECTIONAL_LIGHT and SPOT_LIGHT) are defined by the program
+// registry, to match the Rust side.
+
+struct LightProperties {
+    vec3 position;
+    bool enabled;
+    vec3 direction;
+    int light_type;
+    vec4 color;
+    vec3 attenuation;
+    float spot_inner_cos;
+    float spot_outer_cos;
+    bool casts_shadows;
+    int shadow_map;
+    mat4x4 light_space;
+};
+
+layout (std140) uniform light_list {
+    LightProperties lights[MAX_LIGHTS];
+    vec3 ambient;
+};
+
+// The fraction of a light which reaches a position, after falling off
+// with distance and, for spot lights, with the angle from its axis.
+float light_intensity(LightProperties light, vec3 position) {
+    if (light.light_type == DIRECTIONAL_LIGHT) {
+        return 1.0;
+    }
+
+    vec3 to_light = light.position - position;
+    float distance = length(to_light);
+    float intensity = 1.0 / max(dot(light.attenuation, vec3(1.0, distance, distance * distance)), 0.0001);
+    if (light.light_type == SPOT_LIGHT) {
+        float cos_angle = dot(-to_light / distance, normalize(light.direction));
+        intensity *= smoothstep(light.spot_outer_cos, light.spot_inner_cos, cos_angle);
+    }
+    return intensity;
+}
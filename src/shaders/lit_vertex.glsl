@@ -2,13 +2,7 @@
 
 #version 410 core
 
-const int MAX_LIGHTS = 10;
-struct LightProperties {
-    bool enabled;
-    vec3 position;
-    vec4 color;
-    float specular_exp;
-};
+#include "view_and_projection.glsl"
 
 in vec3 position;
 in vec3 normal;
@@ -16,20 +10,11 @@ in vec4 color;
 
 uniform mat4x4 model;
 uniform mat3x3 model_normal;
-layout (std140) uniform view_and_projection {
-    mat4x4 view;
-    mat4x4 view_inv;
-    mat4x4 projection;
-};
-layout (std140) uniform light_list {
-    LightProperties lights[MAX_LIGHTS];
-};
 
+out vec3 v_position;
 out vec3 v_normal;
 out vec4 v_color;
 out vec3 v_eye_dir;
-out vec3 v_light_dir[MAX_LIGHTS];
-out vec3 v_light_reflect_dir[MAX_LIGHTS];
 
 void main(void) {
     vec4 wld_vert_position4 = model * vec4(position, 1.0);
@@ -44,12 +29,7 @@ void main(void) {
 
     gl_Position = projection * view * wld_vert_position4;
     v_color = color;
+    v_position = wld_vert_position;
     v_eye_dir = wld_vert_eye_dir;
     v_normal = wld_vert_normal;
-    for (int i = 0; i < MAX_LIGHTS; ++i) {
-        if (lights[i].enabled) {
-            v_light_dir[i] = normalize(lights[i].position - wld_vert_position);
-            v_light_reflect_dir[i] = normalize(reflect(-1 * v_light_dir[i], wld_vert_normal));
-        }
-    }
 }
@@ -0,0 +1,16 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+// The material of the object being drawn.
+
+struct Material {
+    vec3 ambient;
+    float shininess;
+    vec3 diffuse;
+    bool use_vertex_color;
+    vec3 specular;
+    vec3 emissive;
+};
+
+layout (std140) uniform material_block {
+    Material material;
+};
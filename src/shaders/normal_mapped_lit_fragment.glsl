@@ -0,0 +1,31 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "lighting.glsl"
+
+in vec3 v_position;
+in vec3 v_normal;
+in vec3 v_tangent;
+in vec3 v_bitangent;
+in vec4 v_color;
+in vec2 v_tex_coords;
+in vec3 v_eye_dir;
+
+uniform sampler2D diffuse_texture;
+uniform sampler2D normal_map;
+
+out vec4 frag_color;
+
+void main(void) {
+    vec4 base_color = texture(diffuse_texture, v_tex_coords);
+    base_color *= material.use_vertex_color ? v_color : vec4(1.0, 1.0, 1.0, v_color.a);
+
+    // The normal map stores tangent-space normals in [0, 1].
+    mat3 tbn = mat3(normalize(v_tangent), normalize(v_bitangent), normalize(v_normal));
+    vec3 map_normal = 2.0 * texture(normal_map, v_tex_coords).xyz - 1.0;
+    vec3 normal = normalize(tbn * map_normal);
+
+    vec3 color = shade(normal, normalize(v_eye_dir), v_position, base_color.rgb);
+    frag_color = vec4(color, base_color.a);
+}
@@ -0,0 +1,47 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "view_and_projection.glsl"
+
+in vec3 position;
+in vec3 normal;
+in vec4 color;
+in vec2 tex_coords;
+in vec4 tangent;
+
+uniform mat4x4 model;
+uniform mat3x3 model_normal;
+
+out vec3 v_position;
+out vec3 v_normal;
+out vec3 v_tangent;
+out vec3 v_bitangent;
+out vec4 v_color;
+out vec2 v_tex_coords;
+out vec3 v_eye_dir;
+
+void main(void) {
+    vec4 wld_vert_position4 = model * vec4(position, 1.0);
+    vec3 wld_vert_position = wld_vert_position4.xyz / wld_vert_position4.w;
+
+    vec4 wld_eye_position4 = view_inv * vec4(0.0, 0.0, 0.0, 1.0);
+    vec3 wld_eye_position = wld_eye_position4.xyz / wld_eye_position4.w;
+
+    // The tangent lies in the surface, so it transforms like a
+    // position rather than like a normal. Its w component is the
+    // handedness of the tangent frame.
+    vec3 wld_vert_normal = normalize(model_normal * normal);
+    vec3 wld_vert_tangent = normalize(mat3(model) * tangent.xyz);
+    wld_vert_tangent = normalize(wld_vert_tangent - dot(wld_vert_tangent, wld_vert_normal) * wld_vert_normal);
+    vec3 wld_vert_bitangent = tangent.w * cross(wld_vert_normal, wld_vert_tangent);
+
+    gl_Position = projection * view * wld_vert_position4;
+    v_position = wld_vert_position;
+    v_normal = wld_vert_normal;
+    v_tangent = wld_vert_tangent;
+    v_bitangent = wld_vert_bitangent;
+    v_color = color;
+    v_tex_coords = tex_coords;
+    v_eye_dir = wld_eye_position - wld_vert_position;
+}
@@ -0,0 +1,18 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "lighting.glsl"
+
+in vec3 v_position;
+in vec3 v_normal;
+in vec4 v_color;
+in vec3 v_eye_dir;
+
+out vec4 frag_color;
+
+void main(void) {
+    vec4 base_color = material.use_vertex_color ? v_color : vec4(1.0, 1.0, 1.0, v_color.a);
+    vec3 color = shade(normalize(v_normal), normalize(v_eye_dir), v_position, base_color.rgb);
+    frag_color = vec4(color, base_color.a);
+}
@@ -0,0 +1,20 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+uniform bool round_points;
+
+in vec4 v_color;
+
+out vec4 FragColor;
+
+void main(void) {
+    if (round_points) {
+        vec2 from_center = 2.0 * gl_PointCoord - vec2(1.0, 1.0);
+        if (dot(from_center, from_center) > 1.0) {
+            discard;
+        }
+    }
+
+    FragColor = v_color;
+}
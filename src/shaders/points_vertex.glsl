@@ -0,0 +1,29 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "view_and_projection.glsl"
+
+in vec3 position;
+in vec4 color;
+
+uniform mat4x4 model;
+uniform float point_size;
+uniform float attenuation_distance;
+
+out vec4 v_color;
+
+void main(void) {
+    vec4 eye_position = view * model * vec4(position, 1.0);
+    gl_Position = projection * eye_position;
+    v_color = color;
+
+    // With attenuation, points are point_size pixels across at
+    // attenuation_distance from the eye, and shrink as they recede.
+    if (attenuation_distance > 0.0) {
+        float distance = max(-eye_position.z, 0.0001);
+        gl_PointSize = max(1.0, point_size * attenuation_distance / distance);
+    } else {
+        gl_PointSize = point_size;
+    }
+}
@@ -0,0 +1,166 @@
+//! A small GLSL preprocessor, run on each shader before it's compiled.
+//! It expands `#include "file.glsl"` lines, and adds `#define`s for the
+//! constants the shaders share with the Rust side after the `#version`
+//! line.
+//!
+//! Each file is only included once per shader, so snippets can include
+//! what they need without clashing. `#line` directives keep the line
+//! numbers in the GL's error messages pointing at the original files:
+//! source string 0 is the shader itself, and `n` is the `n`th file in
+//! `Preprocessed::includes`.
+
+use std::fmt::Write;
+
+use super::ShaderError;
+
+pub struct Preprocessed {
+    pub source: String,
+
+    /// The included files, in the order of their source string
+    /// numbers, starting from 1.
+    pub includes: Vec<String>,
+}
+
+/// Preprocesses `source`, looking up included files with `include`.
+pub fn preprocess<I>(
+    source: &str,
+    include: I,
+    defines: &[(&str, String)],
+) -> Result<Preprocessed, ShaderError>
+where
+    I: Fn(&str) -> Option<String>,
+{
+    let mut expander = Expander {
+        include,
+        defines,
+        output: String::new(),
+        includes: vec![],
+    };
+    if !source.lines().any(is_version) {
+        expander.write_defines(1);
+    }
+    expander.expand(source, 0)?;
+    Ok(Preprocessed {
+        source: expander.output,
+        includes: expander.includes,
+    })
+}
+
+struct Expander<'a, I> {
+    include: I,
+    defines: &'a [(&'a str, String)],
+    output: String,
+    includes: Vec<String>,
+}
+
+impl<'a, I: Fn(&str) -> Option<String>> Expander<'a, I> {
+    fn expand(&mut self, source: &str, string: usize) -> Result<(), ShaderError> {
+        for (i, line) in source.lines().enumerate() {
+            let next_line = i + 2;
+            if line.trim_start().starts_with("#include") {
+                let name = include_name(line).ok_or_else(|| {
+                    ShaderError::Preprocess(format!("Malformed #include: {:?}", line.trim()))
+                })?;
+                if self.includes.iter().any(|included| included == name) {
+                    // Keep the line, so the next one's number is right.
+                    self.output.push('\n');
+                    continue;
+                }
+                let text = (self.include)(name).ok_or_else(|| {
+                    ShaderError::Preprocess(format!("Can't find included file {:?}", name))
+                })?;
+                self.includes.push(name.to_string());
+                writeln!(self.output, "#line 1 {}", self.includes.len()).unwrap();
+                self.expand(&text, self.includes.len())?;
+                writeln!(self.output, "#line {} {}", next_line, string).unwrap();
+            } else {
+                self.output.push_str(line);
+                self.output.push('\n');
+                if string == 0 && is_version(line) {
+                    self.write_defines(next_line);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the defines, then resets the line number to `next_line`
+    // in the shader itself.
+    fn write_defines(&mut self, next_line: usize) {
+        for (name, value) in self.defines.iter() {
+            writeln!(self.output, "#define {} {}", name, value).unwrap();
+        }
+        writeln!(self.output, "#line {} 0", next_line).unwrap();
+    }
+}
+
+fn is_version(line: &str) -> bool {
+    line.trim_start().starts_with("#version")
+}
+
+// The file name in an `#include "name"` line.
+fn include_name(line: &str) -> Option<&str> {
+    let quoted = line.trim().strip_prefix("#include")?.trim();
+    quoted.strip_prefix('"')?.strip_suffix('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::preprocess;
+
+    fn files(name: &str) -> Option<String> {
+        let files: HashMap<&str, &str> = [
+            ("a.glsl", "float a;\n"),
+            ("b.glsl", "#include \"a.glsl\"\nfloat b;\n"),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        files.get(name).map(|text| text.to_string())
+    }
+
+    #[test]
+    fn defines_after_version() {
+        let defines = [("MAX_LIGHTS", "10".to_string())];
+        let rv = preprocess("// x\n#version 410 core\nvoid main() {}\n", files, &defines).unwrap();
+        assert_eq!(
+            rv.source,
+            "// x\n#version 410 core\n#define MAX_LIGHTS 10\n#line 3 0\nvoid main() {}\n"
+        );
+
+        let rv = preprocess("void main() {}\n", files, &defines).unwrap();
+        assert_eq!(
+            rv.source,
+            "#define MAX_LIGHTS 10\n#line 1 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn includes_once() {
+        let source = "#include \"b.glsl\"\n  #include \"a.glsl\"\nvoid main() {}\n";
+        let rv = preprocess(source, files, &[]).unwrap();
+        assert_eq!(rv.includes, vec!["b.glsl", "a.glsl"]);
+        assert_eq!(
+            rv.source,
+            concat!(
+                "#line 1 0\n",
+                "#line 1 1\n",
+                "#line 1 2\n",
+                "float a;\n",
+                "#line 2 1\n",
+                "float b;\n",
+                "#line 2 0\n",
+                "\n",
+                "void main() {}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn bad_includes() {
+        assert!(preprocess("#include \"c.glsl\"\n", files, &[]).is_err());
+        assert!(preprocess("#include a.glsl\n", files, &[]).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Builds the built-in programs by name, sharing each one between
+//! everything that asks for it.
+
+use std::{collections::HashMap, rc::Rc};
+
+use glium::{backend::Facade, Program};
+
+use super::{ShaderError, ShaderSource};
+
+#[derive(Default)]
+pub struct ProgramRegistry {
+    programs: HashMap<&'static str, (&'static ShaderSource, Rc<Program>)>,
+}
+
+impl ProgramRegistry {
+    pub fn new() -> ProgramRegistry {
+        Default::default()
+    }
+
+    /// The program called `name`, e.g. "lit" or "phong_lit", built the
+    /// first time it's asked for.
+    pub fn get<F: Facade>(&mut self, facade: &F, name: &str) -> Result<Rc<Program>, ShaderError> {
+        if let Some((_, program)) = self.programs.get(name) {
+            return Ok(program.clone());
+        }
+        let source =
+            super::source(name).ok_or_else(|| ShaderError::UnknownProgram(name.to_string()))?;
+        let program = Rc::new(source.build(facade)?);
+        self.programs.insert(source.name, (source, program.clone()));
+        Ok(program)
+    }
+
+    /// Replaces a program with a new version, e.g. after it's been
+    /// reloaded, so later `get`s return the new one.
+    pub fn replace(&mut self, old: &Rc<Program>, new: &Rc<Program>) {
+        for (_, program) in self.programs.values_mut() {
+            if Rc::ptr_eq(program, old) {
+                *program = new.clone();
+            }
+        }
+    }
+
+    /// The programs built so far, along with their sources.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static ShaderSource, &Rc<Program>)> {
+        self.programs
+            .values()
+            .map(|(source, program)| (*source, program))
+    }
+}
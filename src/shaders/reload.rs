@@ -0,0 +1,296 @@
+//! Rebuilds programs when their GLSL files change, so shaders can be
+//! tweaked without recompiling. Meant for development: it polls the
+//! files' modification times a few times a second, and reads them from
+//! the source tree.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use glium::{backend::Facade, Program};
+
+use super::{preprocess::preprocess, ProgramRegistry, ShaderSource, INCLUDES};
+use crate::scene::Scene;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct ShaderReloader {
+    dir: PathBuf,
+    watched: Vec<Watched<Program>>,
+    last_poll: Option<Instant>,
+}
+
+struct Watched<P> {
+    source: &'static ShaderSource,
+    program: Rc<P>,
+
+    // The program's shaders, and the files they include.
+    files: Vec<String>,
+
+    // The latest modification time of the program's files when it
+    // was last built, or when it started being watched.
+    modified: Option<SystemTime>,
+}
+
+impl ShaderReloader {
+    /// Watches the shader files in `dir`, usually `src/shaders`.
+    pub fn new<P: AsRef<Path>>(dir: P) -> ShaderReloader {
+        ShaderReloader {
+            dir: dir.as_ref().to_path_buf(),
+            watched: vec![],
+            last_poll: None,
+        }
+    }
+
+    /// Rebuilds `program` from `source`'s files, or the files they
+    /// include, when they change.
+    pub fn watch(&mut self, source: &'static ShaderSource, program: Rc<Program>) {
+        let files = program_files(&self.dir, source);
+        let modified = latest_modified(&files, |file| modified(&self.dir.join(file)));
+        self.watched.push(Watched {
+            source,
+            program,
+            files,
+            modified,
+        });
+    }
+
+    /// Rebuilds the programs whose files have changed, and returns the
+    /// old and new versions of each. If a program doesn't compile, it
+    /// prints the error and keeps the old version. Does nothing if the
+    /// files were checked less than `POLL_INTERVAL` ago.
+    pub fn reload<F: Facade>(&mut self, facade: &F) -> Vec<(Rc<Program>, Rc<Program>)> {
+        let now = Instant::now();
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < POLL_INTERVAL {
+                return vec![];
+            }
+        }
+        self.last_poll = Some(now);
+
+        // Programs share most of their includes, so each file is only
+        // checked once per poll.
+        let dir = &self.dir;
+        let mut checked: HashMap<String, Option<SystemTime>> = HashMap::new();
+        reload_changed(
+            &mut self.watched,
+            |source| program_files(dir, source),
+            |file| {
+                *checked
+                    .entry(file.to_string())
+                    .or_insert_with(|| modified(&dir.join(file)))
+            },
+            |source| {
+                let (vertex, fragment) = read_source(dir, source)?;
+                source
+                    .build_from(facade, &vertex, &fragment, |file| {
+                        fs::read_to_string(dir.join(file)).ok()
+                    })
+                    .map_err(|e| io::Error::other(e.to_string()))
+            },
+        )
+    }
+
+    /// Rebuilds the changed programs, and swaps them into the registry
+    /// and the scene.
+    pub fn update<F: Facade>(
+        &mut self,
+        facade: &F,
+        programs: &mut ProgramRegistry,
+        scene: &mut Scene,
+    ) {
+        for (old, new) in self.reload(facade) {
+            programs.replace(&old, &new);
+            scene.replace_program(&old, &new);
+        }
+    }
+}
+
+// Rebuilds the watched programs whose files' modification times have
+// changed with `build`, keeping the old ones if it fails. The files
+// are looked up again with `files` after a change, since it might have
+// added or removed an `#include`.
+fn reload_changed<P, L, M, B>(
+    watched: &mut [Watched<P>],
+    mut files: L,
+    mut modified: M,
+    mut build: B,
+) -> Vec<(Rc<P>, Rc<P>)>
+where
+    L: FnMut(&ShaderSource) -> Vec<String>,
+    M: FnMut(&str) -> Option<SystemTime>,
+    B: FnMut(&ShaderSource) -> io::Result<P>,
+{
+    let mut rv = vec![];
+    for watched in watched.iter_mut() {
+        let source = watched.source;
+        if latest_modified(&watched.files, &mut modified) == watched.modified {
+            continue;
+        }
+        watched.files = files(source);
+        watched.modified = latest_modified(&watched.files, &mut modified);
+
+        match build(source) {
+            Ok(program) => {
+                let program = Rc::new(program);
+                let old = std::mem::replace(&mut watched.program, program.clone());
+                rv.push((old, program));
+            }
+            Err(e) => eprintln!(
+                "Error reloading {} / {}: {}",
+                source.vertex_file, source.fragment_file, e
+            ),
+        }
+    }
+    rv
+}
+
+// The files a program is built from in `dir`: its shaders, and the
+// files they include. If they can't be preprocessed, any of the shared
+// files might be the problem, so they're all included.
+fn program_files(dir: &Path, source: &ShaderSource) -> Vec<String> {
+    let mut files = vec![
+        source.vertex_file.to_string(),
+        source.fragment_file.to_string(),
+    ];
+    let include = |file: &str| fs::read_to_string(dir.join(file)).ok();
+    let preprocessed = read_source(dir, source)
+        .ok()
+        .and_then(|(vertex, fragment)| {
+            let vertex = preprocess(&vertex, include, &[]).ok()?;
+            let fragment = preprocess(&fragment, include, &[]).ok()?;
+            Some((vertex, fragment))
+        });
+    let includes: Vec<String> = match preprocessed {
+        Some((vertex, fragment)) => vertex
+            .includes
+            .into_iter()
+            .chain(fragment.includes)
+            .collect(),
+        None => INCLUDES.iter().map(|(file, _)| file.to_string()).collect(),
+    };
+    for file in includes {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+fn latest_modified<M>(files: &[String], mut modified: M) -> Option<SystemTime>
+where
+    M: FnMut(&str) -> Option<SystemTime>,
+{
+    files.iter().filter_map(|file| modified(file)).max()
+}
+
+/// The vertex and fragment shaders of a program, read from `dir`.
+pub fn read_source(dir: &Path, source: &ShaderSource) -> io::Result<(String, String)> {
+    Ok((
+        fs::read_to_string(dir.join(source.vertex_file))?,
+        fs::read_to_string(dir.join(source.fragment_file))?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        io,
+        path::Path,
+        rc::Rc,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{program_files, read_source, reload_changed, Watched};
+    use crate::shaders::{ShaderSource, LIT, POINTS};
+
+    #[test]
+    fn read_built_in_source() {
+        let dir = Path::new("src/shaders");
+        for source in [&LIT, &POINTS].iter() {
+            let (vertex, fragment) = read_source(dir, source).unwrap();
+            assert_eq!(source.vertex, vertex);
+            assert_eq!(source.fragment, fragment);
+        }
+        assert!(read_source(Path::new("nowhere"), &LIT).is_err());
+    }
+
+    #[test]
+    fn files_are_the_shaders_and_their_includes() {
+        let dir = Path::new("src/shaders");
+        assert_eq!(
+            vec![
+                "points_vertex.glsl",
+                "points_fragment.glsl",
+                "view_and_projection.glsl"
+            ],
+            program_files(dir, &POINTS)
+        );
+        let lit = program_files(dir, &LIT);
+        assert_eq!(7, lit.len());
+        for file in [
+            "lighting.glsl",
+            "lights.glsl",
+            "material.glsl",
+            "shadows.glsl",
+        ]
+        .iter()
+        {
+            assert!(lit.iter().any(|f| f == file), "{}", file);
+        }
+    }
+
+    #[test]
+    fn failed_builds_keep_the_old_program() {
+        let start = SystemTime::UNIX_EPOCH;
+        let original = Rc::new(1);
+        let mut watched = [Watched {
+            source: &LIT,
+            program: original.clone(),
+            files: vec!["lit_fragment.glsl".to_string()],
+            modified: Some(start),
+        }];
+        let now = Cell::new(start);
+        let builds = Cell::new(0);
+        let files = |_: &ShaderSource| vec!["lit_fragment.glsl".to_string()];
+
+        // Nothing's changed, so nothing's built.
+        let unchanged = reload_changed(&mut watched, files, |_| Some(now.get()), |_| Ok(2));
+        assert!(unchanged.is_empty());
+
+        // A change which doesn't compile leaves the old program.
+        now.set(start + Duration::from_secs(1));
+        let failed = reload_changed(
+            &mut watched,
+            files,
+            |_| Some(now.get()),
+            |_| {
+                builds.set(builds.get() + 1);
+                Err(io::Error::other("syntax error"))
+            },
+        );
+        assert!(failed.is_empty());
+        assert_eq!(1, builds.get());
+        assert!(Rc::ptr_eq(&original, &watched[0].program));
+
+        // It isn't retried until the files change again, and then
+        // the fixed program replaces it.
+        let retried = reload_changed(&mut watched, files, |_| Some(now.get()), |_| Ok(2));
+        assert!(retried.is_empty());
+        now.set(start + Duration::from_secs(2));
+        let fixed = reload_changed(&mut watched, files, |_| Some(now.get()), |_| Ok(3));
+        assert_eq!(1, fixed.len());
+        assert!(Rc::ptr_eq(&original, &fixed[0].0));
+        assert_eq!(3, *fixed[0].1);
+        assert!(Rc::ptr_eq(&fixed[0].1, &watched[0].program));
+    }
+}
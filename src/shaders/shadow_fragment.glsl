@@ -0,0 +1,7 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+// Only the depth is written.
+void main(void) {
+}
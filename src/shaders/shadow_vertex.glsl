@@ -0,0 +1,12 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+in vec3 position;
+
+uniform mat4x4 model;
+uniform mat4x4 light_space;
+
+void main(void) {
+    gl_Position = light_space * model * vec4(position, 1.0);
+}
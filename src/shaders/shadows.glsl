@@ -0,0 +1,52 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+// Shadow map lookups.
+
+#include "lights.glsl"
+
+const float SHADOW_BIAS = 0.002;
+uniform sampler2DShadow shadow_map0;
+uniform sampler2DShadow shadow_map1;
+uniform sampler2DShadow shadow_map2;
+uniform sampler2DShadow shadow_map3;
+uniform bool receive_shadows;
+
+float shadow_sample(int shadow_map, vec3 coords) {
+    if (shadow_map == 0) {
+        return texture(shadow_map0, coords);
+    } else if (shadow_map == 1) {
+        return texture(shadow_map1, coords);
+    } else if (shadow_map == 2) {
+        return texture(shadow_map2, coords);
+    } else {
+        return texture(shadow_map3, coords);
+    }
+}
+
+// The fraction of a light which isn't blocked on its way to a
+// position, averaged over the 3x3 shadow map texels around it.
+float light_visibility(LightProperties light, vec3 position) {
+    if (!receive_shadows || light.shadow_map < 0) {
+        return 1.0;
+    }
+
+    vec4 light_position4 = light.light_space * vec4(position, 1.0);
+    vec3 coords = 0.5 * light_position4.xyz / light_position4.w + 0.5;
+    if (any(lessThan(coords, vec3(0.0))) || any(greaterThan(coords.xy, vec2(1.0)))) {
+        return 1.0;
+    }
+
+    // Past the far plane, a position is behind all the casters, so it's
+    // shadowed wherever one of them is in the way.
+    coords.z = min(coords.z, 1.0);
+
+    vec2 texel = 1.0 / vec2(textureSize(shadow_map0, 0));
+    float visibility = 0.0;
+    for (int x = -1; x <= 1; ++x) {
+        for (int y = -1; y <= 1; ++y) {
+            vec2 offset = vec2(x, y) * texel;
+            visibility += shadow_sample(light.shadow_map, vec3(coords.xy + offset, coords.z - SHADOW_BIAS));
+        }
+    }
+    return visibility / 9.0;
+}
@@ -0,0 +1,22 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+#version 410 core
+
+#include "lighting.glsl"
+
+in vec3 v_position;
+in vec3 v_normal;
+in vec4 v_color;
+in vec2 v_tex_coords;
+in vec3 v_eye_dir;
+
+uniform sampler2D diffuse_texture;
+
+out vec4 frag_color;
+
+void main(void) {
+    vec4 base_color = texture(diffuse_texture, v_tex_coords);
+    base_color *= material.use_vertex_color ? v_color : vec4(1.0, 1.0, 1.0, v_color.a);
+    vec3 color = shade(normalize(v_normal), normalize(v_eye_dir), v_position, base_color.rgb);
+    frag_color = vec4(color, base_color.a);
+}
@@ -2,16 +2,13 @@
 
 #version 410 core
 
+#include "view_and_projection.glsl"
+
 in vec3 position;
 in vec4 color;
 
 uniform mat4x4 model;
 uniform mat3x3 model_normal;
-layout (shared) uniform view_and_projection {
-    mat4x4 view;
-    mat4x4 view_inv;
-    mat4x4 projection;
-};
 
 out vec4 v_color;
 
@@ -0,0 +1,7 @@
+// -*- mode: glsl; c-basic-offset: 4; -*-
+
+layout (std140) uniform view_and_projection {
+    mat4x4 view;
+    mat4x4 view_inv;
+    mat4x4 projection;
+};
@@ -0,0 +1,29 @@
+use std::io;
+
+use glium::{
+    backend::Facade,
+    texture::{MipmapsOption, RawImage2d, SrgbTexture2d, Texture2d},
+};
+
+/// Loads an image file (png, jpeg, etc.) as an sRGB texture with a
+/// full chain of generated mipmaps.
+pub fn load_texture<F: Facade>(facade: &F, filename: &str) -> io::Result<SrgbTexture2d> {
+    let image = image::open(filename).map_err(io::Error::other)?.to_rgba8();
+    let dimensions = image.dimensions();
+
+    // Images are stored top row first, but OpenGL expects the bottom
+    // row first.
+    let raw = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions);
+    SrgbTexture2d::with_mipmaps(facade, raw, MipmapsOption::AutoGeneratedMipmaps)
+        .map_err(io::Error::other)
+}
+
+/// Loads a tangent-space normal map. Unlike color textures, normal
+/// maps hold vectors rather than colors, so they aren't sRGB-decoded.
+pub fn load_normal_map<F: Facade>(facade: &F, filename: &str) -> io::Result<Texture2d> {
+    let image = image::open(filename).map_err(io::Error::other)?.to_rgba8();
+    let dimensions = image.dimensions();
+    let raw = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions);
+    Texture2d::with_mipmaps(facade, raw, MipmapsOption::AutoGeneratedMipmaps)
+        .map_err(io::Error::other)
+}